@@ -1,60 +1,352 @@
-use reqwest::{Method, RequestBuilder};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::time::Duration;
+use dns_provider::{ProviderHeaders, RateLimitHeaders, RetryPolicy};
+use crate::CloudflareError;
+
+pub const BASE_URL: &str = "https://api.cloudflare.com/client/v4";
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+/// default page size of the zone and the `dns_records` listings, zones are
+/// never listed with more as it is the most cloudflare allows for them
+pub const PER_PAGE: u32 = 50;
+
+/// The rate limit and credential headers of the cloudflare api.
+const HEADERS: ProviderHeaders = ProviderHeaders {
+    rate_limit: RateLimitHeaders::Combined("Ratelimit"),
+    credentials: &["authorization", "x-auth-key", "x-auth-email"],
+};
 
 pub mod zones;
 pub mod records;
+pub mod settings;
+pub mod user;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ResponseError {
     pub code: i32,
     pub message: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Response<T> {
     pub success: bool,
     pub errors: Option<Vec<ResponseError>>,
     pub messages: Option<Vec<String>>,
     pub result: Option<Vec<T>>,
+    pub result_info: Option<ResultInfo>,
 }
 
-#[derive(Deserialize, Debug)]
+/// Pagination of a listing.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ResultInfo {
+    pub page: u32,
+    pub per_page: u32,
+    #[serde(default)]
+    pub count: u32,
+    #[serde(default)]
+    pub total_count: u32,
+    #[serde(default)]
+    pub total_pages: u32,
+}
+
+impl ResultInfo {
+    pub fn is_last_page(&self) -> bool {
+        self.page >= self.total_pages
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 pub struct SingleResult<T> {
     pub success: bool,
     pub result: Option<T>,
     pub errors: Option<Vec<ResponseError>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct OnlyResult<T> {
     pub result: T,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct OnlyId {
     pub id: String,
 }
 
-type ResultResponse<T> = Result<Response<T>, Box<dyn std::error::Error>>;
-type SingleResultResponse<T> = Result<SingleResult<T>, Box<dyn std::error::Error>>;
-type OnlyResultResponse<T> = Result<OnlyResult<T>, Box<dyn std::error::Error>>;
+/// The part of every cloudflare response that tells if the call succeeded.
+#[derive(Deserialize, Debug)]
+struct Outcome {
+    #[serde(default = "succeeded")]
+    success: bool,
+    errors: Option<Vec<ResponseError>>,
+}
+
+fn succeeded() -> bool {
+    true
+}
+
+type ResultResponse<T> = Result<Response<T>, CloudflareError>;
+type SingleResultResponse<T> = Result<SingleResult<T>, CloudflareError>;
+type OnlyResultResponse<T> = Result<OnlyResult<T>, CloudflareError>;
+
+#[derive(Clone)]
+pub enum Credentials {
+    /// scoped api token, sent as `Authorization: Bearer`
+    Token(String),
+    /// legacy global api key, sent as `X-Auth-Key` and `X-Auth-Email`
+    Key { key: String, email: String },
+}
+
+impl Credentials {
+    /// Reads `CLOUDFLARE_API_TOKEN`, or `CLOUDFLARE_KEY` and `CLOUDFLARE_EMAIL` if no token is set.
+    pub fn from_env() -> Result<Credentials, CloudflareError> {
+        if let Ok(token) = env::var("CLOUDFLARE_API_TOKEN") {
+            return Ok(Credentials::Token(token));
+        }
+        let key = env::var("CLOUDFLARE_KEY")
+            .map_err(|_| CloudflareError::Auth(String::from("Neither CLOUDFLARE_API_TOKEN nor CLOUDFLARE_KEY is set")))?;
+        let email = env::var("CLOUDFLARE_EMAIL")
+            .map_err(|_| CloudflareError::Auth(String::from("CLOUDFLARE_EMAIL is not set")))?;
+        Ok(Credentials::Key { key, email })
+    }
+}
+
+/// Client of the cloudflare api.
+///
+/// Clones share the same connection pool, so one client per account is enough.
+#[derive(Clone)]
+pub struct CloudflareClient {
+    client: reqwest::Client,
+    base_url: String,
+    credentials: Credentials,
+    per_page: u32,
+    retry: RetryPolicy,
+}
+
+pub struct CloudflareClientBuilder {
+    credentials: Credentials,
+    base_url: String,
+    timeout: Duration,
+    per_page: u32,
+    retry: RetryPolicy,
+}
+
+impl CloudflareClientBuilder {
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = String::from(base_url.trim_end_matches('/'));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Page size used by the listings that fetch all pages.
+    pub fn per_page(mut self, per_page: u32) -> Self {
+        self.per_page = per_page;
+        self
+    }
+
+    /// Retries of rate limited and failed requests, see [`RetryPolicy`].
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<CloudflareClient, CloudflareError> {
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()?;
+        Ok(CloudflareClient {
+            client,
+            base_url: self.base_url,
+            credentials: self.credentials,
+            per_page: self.per_page,
+            retry: self.retry,
+        })
+    }
+}
+
+impl CloudflareClient {
+    pub fn builder(credentials: Credentials) -> CloudflareClientBuilder {
+        CloudflareClientBuilder {
+            credentials,
+            base_url: String::from(BASE_URL),
+            timeout: TIMEOUT,
+            per_page: PER_PAGE,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn new(credentials: Credentials) -> Result<CloudflareClient, CloudflareError> {
+        CloudflareClient::builder(credentials).build()
+    }
+
+    /// Creates a client with the credentials of the env, see [`Credentials::from_env`].
+    pub fn from_env() -> Result<CloudflareClient, CloudflareError> {
+        CloudflareClient::new(Credentials::from_env()?)
+    }
+
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    pub fn request(&self, path: &str, method: Method) -> RequestBuilder {
+        let url = format!("{}/{}", self.base_url, path);
+        let request = self.client
+            .request(method, url)
+            .header("Content-Type", "application/json");
+        match &self.credentials {
+            Credentials::Token(token) => request.bearer_auth(token),
+            Credentials::Key { key, email } => request
+                .header("X-Auth-Key", key.as_str())
+                .header("X-Auth-Email", email.as_str()),
+        }
+    }
+
+    /// Fetches one page of a listing, `page` starts at 1.
+    pub async fn get_page<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)], page: u32, per_page: u32) -> ResultResponse<T> {
+        let client = self.request(path, Method::GET)
+            .query(query)
+            .query(&[("page", page), ("per_page", per_page)]);
+        self.send(client).await
+    }
+
+    /// Fetches the pages of a listing one after another and returns all
+    /// results in one response, with the `result_info` of the last page.
+    pub async fn get_all_pages<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)], per_page: u32) -> ResultResponse<T> {
+        let mut results = Vec::new();
+        let mut page = 1;
+        loop {
+            let response = self.get_page::<T>(path, query, page, per_page).await?;
+            let result = response.result.unwrap_or_default();
+            let last = result.is_empty() || response.result_info.as_ref().map(ResultInfo::is_last_page).unwrap_or(true);
+            results.extend(result);
+            if last {
+                return Ok(Response { result: Some(results), ..response });
+            }
+            page += 1;
+        }
+    }
+
+    /// Sends the request and decodes the json body if the call succeeded.
+    ///
+    /// Cloudflare reports errors with `"success": false` and a list of errors,
+    /// sometimes even with a successful status, so both are checked.
+    pub async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, CloudflareError> {
+        let response = self.retry.execute(&HEADERS, request).await?;
+        let status = response.status();
+        let retry_after = HEADERS.retry_after(response.headers());
+        let body = response.bytes().await?;
+        let outcome = serde_json::from_slice::<Outcome>(&body);
+        if status.is_success() {
+            return match outcome {
+                Ok(Outcome { success: false, errors }) => Err(CloudflareError::Api {
+                    status: status.as_u16(),
+                    errors: errors.unwrap_or_default(),
+                }),
+                _ => Ok(serde_json::from_slice(&body)?),
+            };
+        }
+        let errors = outcome.ok().and_then(|outcome| outcome.errors).unwrap_or_default();
+        let message = if errors.is_empty() {
+            String::from_utf8_lossy(&body).into_owned()
+        } else {
+            errors.iter().map(|x| format!("{}: {}", x.code, x.message)).collect::<Vec<_>>().join(", ")
+        };
+        Err(match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => CloudflareError::Auth(message),
+            StatusCode::TOO_MANY_REQUESTS => CloudflareError::RateLimited { retry_after },
+            _ if !errors.is_empty() => CloudflareError::Api { status: status.as_u16(), errors },
+            _ => CloudflareError::Status { status: status.as_u16(), message },
+        })
+    }
+}
+
+#[cfg(test)]
+pub fn test_client() -> CloudflareClient {
+    let credentials = Credentials::Key {
+        key: String::from("key"),
+        email: String::from("mail@example.com"),
+    };
+    CloudflareClient::builder(credentials)
+        .base_url(&mockito::server_url())
+        .retry(RetryPolicy::never())
+        .build()
+        .unwrap()
+}
+
+#[cfg(test)]
+pub fn test_token_client() -> CloudflareClient {
+    CloudflareClient::builder(Credentials::Token(String::from("token")))
+        .base_url(&mockito::server_url())
+        .retry(RetryPolicy::never())
+        .build()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_retry_rate_limited_create() {
+        let limited = mock("POST", "/zones/retry/dns_records")
+            .with_status(429)
+            .with_header("Retry-After", "0")
+            .with_body(r#"{"success": false, "errors": [{"code": 10000, "message": "Rate limited"}]}"#)
+            .expect(1)
+            .create();
+        let created = mock("POST", "/zones/retry/dns_records")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "result": {"id": "retried"}}"#)
+            .expect(1)
+            .create();
+        let client = CloudflareClient::builder(Credentials::Token(String::from("token")))
+            .base_url(&mockito::server_url())
+            .retry(RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::default() })
+            .build()
+            .unwrap();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let request = client.request("zones/retry/dns_records", Method::POST).body("{}");
+            let response = client.send::<SingleResult<OnlyId>>(request).await.unwrap();
+            assert_eq!("retried", response.result.unwrap().id);
+        });
+        limited.assert();
+        created.assert();
+    }
 
-pub fn cloudflare_client(path: &str, method: Method) -> RequestBuilder {
-    let key = env::var("CLOUDFLARE_KEY").unwrap();
-    let email = env::var("CLOUDFLARE_EMAIL").unwrap();
+    #[test]
+    fn test_no_retry_without_policy() {
+        let m = mock("GET", "/zones/no-retry")
+            .match_query(Matcher::Any)
+            .with_status(503)
+            .expect(1)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let client = test_client();
+            let error = client.send::<OnlyResult<OnlyId>>(client.request("zones/no-retry", Method::GET)).await.err().unwrap();
+            assert!(matches!(error, CloudflareError::Status { status: 503, .. }));
+        });
+        m.assert();
+    }
 
-    #[cfg(not(test))]
-    let url = "https://api.cloudflare.com/client/v4";
-    #[cfg(test)]
-    let url = &mockito::server_url();
+    #[test]
+    fn test_redacted_headers() {
+        let request = test_client().request("zones", Method::GET).build().unwrap();
+        let headers = HEADERS.redacted_headers(request.headers());
+        assert!(headers.contains(&(String::from("content-type"), String::from("application/json"))));
+        assert!(headers.contains(&(String::from("x-auth-key"), String::from("[redacted]"))));
+        assert!(headers.contains(&(String::from("x-auth-email"), String::from("[redacted]"))));
 
-    let url = format!("{}/{}", url, path);
-    // todo: enable this for a verbose version
-    // println!("{}: {}", method.as_str(), &url);
-    reqwest::Client::new()
-        .request(method, url)
-        .header("X-Auth-Key", key.as_str())
-        .header("X-Auth-Email", email.as_str())
-        .header("Content-Type", "application/json")
+        let request = test_token_client().request("zones", Method::GET).build().unwrap();
+        let headers = HEADERS.redacted_headers(request.headers());
+        assert!(headers.contains(&(String::from("authorization"), String::from("[redacted]"))));
+    }
 }
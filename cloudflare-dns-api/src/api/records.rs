@@ -1,119 +1,198 @@
-use reqwest::StatusCode;
-use crate::{ResultResponseError};
 use super::*;
+use chrono::{DateTime, Utc};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Record {
     pub id: String,
     #[serde(rename = "type")]
     pub record_type: String,
     pub name: String,
+    /// the value, cloudflare also fills it for records with structured `data`
+    #[serde(default)]
     pub content: String,
+    /// MX, URI and SRV records
+    pub priority: Option<u16>,
+    /// structured value of SRV, CAA, TLSA, SMIMEA, HTTPS, SVCB and similar records
+    pub data: Option<RecordData>,
     pub proxiable: Option<bool>,
     pub proxied: Option<bool>,
-    pub ttl: i16,
+    /// seconds, 1 means automatic
+    pub ttl: u32,
+    #[serde(default)]
     pub locked: bool,
+    #[serde(default)]
     pub zone_id: String,
+    #[serde(default)]
     pub zone_name: String,
-    pub created_on: String,
-    pub modified_on: String,
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub meta: Option<RecordMeta>,
+    pub created_on: DateTime<Utc>,
+    pub modified_on: DateTime<Utc>,
 }
 
-#[derive(Deserialize, Serialize)]
+/// Structured value of a record, the variant follows from the fields of the
+/// `data` object, records cloudflare adds later keep their raw json.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum RecordData {
+    /// TLSA and SMIMEA
+    Tlsa {
+        usage: u8,
+        selector: u8,
+        matching_type: u8,
+        certificate: String,
+    },
+    Srv {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        service: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        proto: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    /// HTTPS and SVCB, `value` holds the service parameters, e.g. `alpn="h3,h2"`
+    Svcb {
+        priority: u16,
+        target: String,
+        value: String,
+    },
+    Caa {
+        flags: u8,
+        tag: String,
+        value: String,
+    },
+    Other(serde_json::Value),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct RecordMeta {
+    pub auto_added: Option<bool>,
+    pub managed_by_apps: Option<bool>,
+    pub managed_by_argo_tunnel: Option<bool>,
+    pub source: Option<String>,
+}
+
+/// Fields of a new record, or the fields to change of an existing one.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
 pub struct RecordCreate {
-    #[serde(rename = "type")]
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
     pub record_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
-    pub ttl: Option<i16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<RecordData>,
+    /// seconds, 1 lets cloudflare choose
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    /// whether the traffic goes through cloudflare, only for A, AAAA and CNAME records
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
 }
 
-pub async fn get_all_records(zone_id: String) -> ResultResponse<Record> {
-    let url = format!("zones/{}/dns_records", zone_id);
-    let client = cloudflare_client(url.as_str(), Method::GET);
-    let response = client.send().await?;
-    let response = response.json::<Response<Record>>().await?;
-    if response.success {
-        Ok(response)
-    } else {
-        let error = ResultResponseError::from(response);
-        Result::Err(Box::from(error))
+impl CloudflareClient {
+    /// Fetches the records of all pages, see [`CloudflareClientBuilder::per_page`].
+    pub async fn get_all_records(&self, zone_id: String) -> ResultResponse<Record> {
+        let url = format!("zones/{}/dns_records", zone_id);
+        self.get_all_pages(url.as_str(), &[], self.per_page).await
     }
-}
 
-pub async fn create_record(name: &str, record_type: &str, value: &str, zone_id: String) -> SingleResultResponse<Record> {
-    let url = format!("zones/{}/dns_records", zone_id);
-    let new_record = RecordCreate {
-        record_type: Option::Some(String::from(record_type)),
-        content: Option::Some(String::from(value)),
-        name: Option::Some(String::from(name)),
-        ttl: Option::None,
-    };
-    let client = cloudflare_client(url.as_str(), Method::POST)
-        .json(&new_record);
-    let result = client.send().await?.json::<SingleResult<Record>>().await?;
-    if result.success {
-        Ok(result)
-    } else {
-        let error = ResultResponseError::from(result);
-        Result::Err(Box::from(error))
+    /// Fetches a single page of records, `page` starts at 1.
+    pub async fn get_records_page(&self, zone_id: &str, page: u32, per_page: u32) -> ResultResponse<Record> {
+        let url = format!("zones/{}/dns_records", zone_id);
+        self.get_page(url.as_str(), &[], page, per_page).await
     }
-}
 
-pub async fn delete_record(zone_id: &str, record_id: &str) -> OnlyResultResponse<OnlyId> {
-    let url = format!("zones/{}/dns_records/{}", zone_id, record_id);
-    let client = cloudflare_client(&*url, Method::DELETE);
-    let result = client.send().await?.json::<OnlyResult<OnlyId>>().await?;
-    // todo: documentation doesnt say response types on error, analyse that
-    Ok(result)
-}
+    pub async fn get_record(&self, zone_id: &str, record_id: &str) -> SingleResultResponse<Record> {
+        let url = format!("zones/{}/dns_records/{}", zone_id, record_id);
+        let client = self.request(url.as_str(), Method::GET);
+        self.send(client).await
+    }
 
+    pub async fn create_record(&self, name: &str, record_type: &str, value: &str, ttl: Option<u32>, proxied: Option<bool>, zone_id: String) -> SingleResultResponse<Record> {
+        let new_record = RecordCreate {
+            record_type: Option::Some(String::from(record_type)),
+            content: Option::Some(String::from(value)),
+            name: Option::Some(String::from(name)),
+            ttl,
+            proxied,
+            ..RecordCreate::default()
+        };
+        self.post_record(zone_id.as_str(), &new_record).await
+    }
 
-pub async fn update_record(record_id: &str, zone_id: String, name: Option<&str>, record_type: Option<&str>, value: Option<&str>, ttl: Option<i16>) -> SingleResultResponse<Record> {
-    let url = format!("zones/{}/dns_records/{}", zone_id, record_id);
-    let update_record = RecordCreate {
-        record_type: match record_type.is_some() {
-            true => Option::Some(String::from(record_type.unwrap())),
-            _ => Option::None,
-        },
-        content: match value.is_some() {
-            true => Option::Some(String::from(value.unwrap())),
-            _ => Option::None,
-        },
-        name: match name.is_some() {
-            true => Option::Some(String::from(name.unwrap())),
-            _ => Option::None,
-        },
-        ttl,
-    };
-    let method = if name.is_some() && record_type.is_some() && value.is_some() && ttl.is_some() {
-        Method::PUT
-    } else {
-        Method::PATCH
-    };
-    let client = cloudflare_client(url.as_str(), method).json(&update_record);
-    let result = client.send().await?.json::<SingleResult<Record>>().await?;
-    if result.success {
-        Ok(result)
-    } else {
-        let error = ResultResponseError::from(result);
-        Result::Err(Box::from(error))
+    /// Creates a record with any of the fields, e.g. an MX record with its
+    /// `priority` or an SRV record with its `data`.
+    pub async fn post_record(&self, zone_id: &str, record: &RecordCreate) -> SingleResultResponse<Record> {
+        let url = format!("zones/{}/dns_records", zone_id);
+        let client = self.request(url.as_str(), Method::POST)
+            .json(record);
+        self.send(client).await
     }
-}
 
+    pub async fn delete_record(&self, zone_id: &str, record_id: &str) -> OnlyResultResponse<OnlyId> {
+        let url = format!("zones/{}/dns_records/{}", zone_id, record_id);
+        let client = self.request(&url, Method::DELETE);
+        self.send(client).await
+    }
+
+    /// Replaces the record if every field is set, otherwise only the set fields are changed.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_record(&self, record_id: &str, zone_id: String, name: Option<&str>, record_type: Option<&str>, value: Option<&str>, ttl: Option<u32>, proxied: Option<bool>) -> SingleResultResponse<Record> {
+        let update_record = RecordCreate {
+            record_type: record_type.map(String::from),
+            content: value.map(String::from),
+            name: name.map(String::from),
+            ttl,
+            proxied,
+            ..RecordCreate::default()
+        };
+        if name.is_some() && record_type.is_some() && value.is_some() && ttl.is_some() && proxied.is_some() {
+            self.put_record(zone_id.as_str(), record_id, &update_record).await
+        } else {
+            self.patch_record(zone_id.as_str(), record_id, &update_record).await
+        }
+    }
+
+    /// Replaces the record, fields that are not set get their defaults.
+    pub async fn put_record(&self, zone_id: &str, record_id: &str, record: &RecordCreate) -> SingleResultResponse<Record> {
+        let url = format!("zones/{}/dns_records/{}", zone_id, record_id);
+        let client = self.request(url.as_str(), Method::PUT).json(record);
+        self.send(client).await
+    }
+
+    /// Changes only the fields of the record that are set.
+    pub async fn patch_record(&self, zone_id: &str, record_id: &str, record: &RecordCreate) -> SingleResultResponse<Record> {
+        let url = format!("zones/{}/dns_records/{}", zone_id, record_id);
+        let client = self.request(url.as_str(), Method::PATCH).json(record);
+        self.send(client).await
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockito::mock;
+    use chrono::TimeZone;
+    use mockito::{mock, Matcher};
     use tokio::runtime::Runtime;
-    use std::env;
 
     #[test]
     fn test_get_all_records() {
-        env::set_var("CLOUDFLARE_KEY", "");
-        env::set_var("CLOUDFLARE_EMAIL", "");
         let _m = mock("GET", "/zones/1-1-1-1/dns_records")
+            .match_query(Matcher::Any)
             .with_header("content-type", "application/json")
             .with_body(r#"{
                 "success": true,
@@ -129,8 +208,8 @@ mod tests {
                         "locked": false,
                         "zone_id": "1-1-1-1",
                         "zone_name": "example.com",
-                        "created_on": "20.05.2021",
-                        "modified_on": "20.05.2021"
+                        "created_on": "2021-05-20T10:00:00.000000Z",
+                        "modified_on": "2021-05-20T10:00:00.000000Z"
                     }
                 ]
             }"#)
@@ -138,20 +217,25 @@ mod tests {
         let runtime = Runtime::new().expect("Init successful");
         runtime.block_on(async move {
             let zone_id = String::from("1-1-1-1");
-            let records = get_all_records(zone_id).await;
+            let records = test_client().get_all_records(zone_id).await;
             let record = Record {
                 id: String::from("1-1-1-1"),
                 record_type: "test".to_string(),
                 name: "test".to_string(),
                 content: "example.com".to_string(),
+                priority: None,
+                data: None,
                 proxiable: Some(false),
                 proxied: Some(false),
                 ttl: 3600,
                 locked: false,
                 zone_id: "1-1-1-1".to_string(),
                 zone_name: "example.com".to_string(),
-                created_on: "20.05.2021".to_string(),
-                modified_on: "20.05.2021".to_string()
+                comment: None,
+                tags: vec![],
+                meta: None,
+                created_on: Utc.with_ymd_and_hms(2021, 5, 20, 10, 0, 0).unwrap(),
+                modified_on: Utc.with_ymd_and_hms(2021, 5, 20, 10, 0, 0).unwrap(),
             };
             assert!(records.is_ok());
             let records = records.unwrap();
@@ -169,9 +253,8 @@ mod tests {
 
     #[test]
     fn test_get_all_records_with_error_data() {
-        env::set_var("CLOUDFLARE_KEY", "");
-        env::set_var("CLOUDFLARE_EMAIL", "");
         let _m = mock("GET", "/zones/1-1-1-1/dns_records")
+            .match_query(Matcher::Any)
             .with_header("content-type", "application/json")
             .with_body(r#"{
                 "success": false,
@@ -181,14 +264,173 @@ mod tests {
         let runtime = Runtime::new().expect("Init successful");
         runtime.block_on(async move {
             let zone_id = String::from("1-1-1-1");
-            let records = get_all_records(zone_id).await;
+            let records = test_client().get_all_records(zone_id).await;
 
             assert!(records.is_err());
-            let error = records.err();
-            assert!(error.is_some());
-            let error: Box<ResultResponseError> = error.unwrap().downcast().unwrap();
-            assert_eq!(1, error.errors.len());
-            assert_eq!("There is an error:\n1: Error happened!", format!("{}", error));
+            let error = records.err().unwrap();
+            match &error {
+                CloudflareError::Api { errors, .. } => assert_eq!(1, errors.len()),
+                _ => panic!("unexpected error {:?}", error),
+            }
+            assert_eq!("Request failed with status 200:\n1: Error happened!", format!("{}", error));
+        });
+    }
+
+    #[test]
+    fn test_get_all_records_of_all_pages() {
+        let record = |id: &str| format!(r#"{{
+            "id": "{}", "type": "A", "name": "home.example.com", "content": "192.0.2.1",
+            "ttl": 1, "locked": false, "zone_id": "2-2-2-2", "zone_name": "example.com",
+            "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+        }}"#, id);
+        let _first = mock("GET", "/zones/2-2-2-2/dns_records")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(String::from("page"), String::from("1")),
+                Matcher::UrlEncoded(String::from("per_page"), String::from("2")),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{
+                "success": true,
+                "result": [{}, {}],
+                "result_info": {{"page": 1, "per_page": 2, "count": 2, "total_count": 3, "total_pages": 2}}
+            }}"#, record("1"), record("2")))
+            .create();
+        let _second = mock("GET", "/zones/2-2-2-2/dns_records")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(String::from("page"), String::from("2")),
+                Matcher::UrlEncoded(String::from("per_page"), String::from("2")),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{
+                "success": true,
+                "result": [{}],
+                "result_info": {{"page": 2, "per_page": 2, "count": 1, "total_count": 3, "total_pages": 2}}
+            }}"#, record("3")))
+            .create();
+        let client = CloudflareClient::builder(Credentials::Token(String::from("token")))
+            .base_url(&mockito::server_url())
+            .per_page(2)
+            .build()
+            .unwrap();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let records = client.get_all_records(String::from("2-2-2-2")).await.unwrap();
+            let ids: Vec<String> = records.result.unwrap().into_iter().map(|record| record.id).collect();
+            assert_eq!(vec!["1", "2", "3"], ids);
+            assert_eq!(3, records.result_info.unwrap().total_count);
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_update_record_only_sends_set_fields() {
+        let _m = mock("PATCH", "/zones/1-1-1-1/dns_records/2-2-2-2")
+            .match_body(Matcher::Json(serde_json::json!({
+                "type": "A", "name": "home", "content": "203.0.113.7", "proxied": true
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": true,
+                "result": {
+                    "id": "2-2-2-2", "type": "A", "name": "home.example.com", "content": "203.0.113.7",
+                    "proxiable": true, "proxied": true, "ttl": 1, "locked": false,
+                    "zone_id": "1-1-1-1", "zone_name": "example.com",
+                    "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+                }
+            }"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let record = test_client()
+                .update_record("2-2-2-2", String::from("1-1-1-1"), Some("home"), Some("A"), Some("203.0.113.7"), None, Some(true))
+                .await
+                .unwrap();
+            assert_eq!(Some(true), record.result.unwrap().proxied);
+        });
+    }
+
+    #[test]
+    fn test_deserialize_typed_records() {
+        let records: Vec<Record> = serde_json::from_str(r#"[
+            {
+                "id": "1", "type": "MX", "name": "example.com", "content": "mail.example.com",
+                "priority": 10, "ttl": 86400, "comment": "primary mail", "tags": ["mail:primary"],
+                "meta": {"auto_added": false, "source": "primary"},
+                "created_on": "2021-05-20T10:00:00.123456Z", "modified_on": "2021-05-21T10:00:00Z"
+            },
+            {
+                "id": "2", "type": "SRV", "name": "_sip._tcp.example.com", "content": "5 5060 sip.example.com",
+                "priority": 10, "ttl": 1,
+                "data": {"priority": 10, "weight": 5, "port": 5060, "target": "sip.example.com"},
+                "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+            },
+            {
+                "id": "3", "type": "CAA", "name": "example.com", "content": "0 issue \"letsencrypt.org\"", "ttl": 1,
+                "data": {"flags": 0, "tag": "issue", "value": "letsencrypt.org"},
+                "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+            },
+            {
+                "id": "4", "type": "HTTPS", "name": "example.com", "content": "1 . alpn=\"h3,h2\"", "ttl": 1,
+                "data": {"priority": 1, "target": ".", "value": "alpn=\"h3,h2\""},
+                "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+            },
+            {
+                "id": "5", "type": "LOC", "name": "example.com", "content": "52 31 0 N 13 24 0 E 30m", "ttl": 1,
+                "data": {"lat_degrees": 52, "long_degrees": 13, "altitude": 30},
+                "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+            }
+        ]"#).unwrap();
+        assert_eq!(86400, records[0].ttl);
+        assert_eq!(Some(10), records[0].priority);
+        assert_eq!(Some(String::from("primary mail")), records[0].comment);
+        assert_eq!(vec![String::from("mail:primary")], records[0].tags);
+        assert_eq!(Some(String::from("primary")), records[0].meta.as_ref().unwrap().source);
+        assert_eq!(123456000, records[0].created_on.timestamp_subsec_nanos());
+        assert!(matches!(records[1].data, Some(RecordData::Srv { port: 5060, .. })));
+        assert!(matches!(&records[2].data, Some(RecordData::Caa { tag, .. }) if tag == "issue"));
+        assert!(matches!(records[3].data, Some(RecordData::Svcb { priority: 1, .. })));
+        assert!(matches!(records[4].data, Some(RecordData::Other(_))));
+    }
+
+    #[test]
+    fn test_post_record_with_data() {
+        let _m = mock("POST", "/zones/1-1-1-1/dns_records")
+            .match_body(Matcher::Json(serde_json::json!({
+                "type": "SRV", "name": "_sip._tcp", "ttl": 3600, "comment": "sip", "tags": ["voip"],
+                "data": {"priority": 10, "weight": 5, "port": 5060, "target": "sip.example.com"}
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": true,
+                "result": {
+                    "id": "6", "type": "SRV", "name": "_sip._tcp.example.com", "content": "5 5060 sip.example.com",
+                    "ttl": 3600, "comment": "sip", "tags": ["voip"],
+                    "data": {"priority": 10, "weight": 5, "port": 5060, "target": "sip.example.com"},
+                    "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+                }
+            }"#)
+            .create();
+        let record = RecordCreate {
+            record_type: Some(String::from("SRV")),
+            name: Some(String::from("_sip._tcp")),
+            data: Some(RecordData::Srv {
+                service: None,
+                proto: None,
+                name: None,
+                priority: 10,
+                weight: 5,
+                port: 5060,
+                target: String::from("sip.example.com"),
+            }),
+            ttl: Some(3600),
+            comment: Some(String::from("sip")),
+            tags: Some(vec![String::from("voip")]),
+            ..RecordCreate::default()
+        };
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let created = test_client().post_record("1-1-1-1", &record).await.unwrap().result.unwrap();
+            assert_eq!(Some(String::from("sip")), created.comment);
+            assert_eq!(record.data, created.data);
+        });
+    }
+}
@@ -0,0 +1,187 @@
+use super::*;
+use chrono::{DateTime, Utc};
+
+/// A setting of a zone, e.g. `min_tls_version`, `value` depends on the setting.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ZoneSetting<T> {
+    pub id: String,
+    pub value: T,
+    #[serde(default)]
+    pub editable: bool,
+    pub modified_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct SettingValue<'a, T> {
+    value: &'a T,
+}
+
+/// Oldest TLS version that visitors can connect with.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinTlsVersion {
+    #[serde(rename = "1.0")]
+    Tls1_0,
+    #[serde(rename = "1.1")]
+    Tls1_1,
+    #[serde(rename = "1.2")]
+    Tls1_2,
+    #[serde(rename = "1.3")]
+    Tls1_3,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DnssecStatus {
+    Active,
+    Pending,
+    Disabled,
+    PendingDisabled,
+    Error,
+    /// a status cloudflare added later
+    #[serde(other)]
+    Other,
+}
+
+/// DNSSEC of a zone, the `ds` record has to be added at the registrar to
+/// finish the activation.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Dnssec {
+    pub status: DnssecStatus,
+    pub flags: Option<u16>,
+    pub algorithm: Option<String>,
+    pub key_type: Option<String>,
+    pub digest_type: Option<String>,
+    pub digest_algorithm: Option<String>,
+    pub digest: Option<String>,
+    pub ds: Option<String>,
+    pub key_tag: Option<u32>,
+    pub public_key: Option<String>,
+    pub modified_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct DnssecUpdate {
+    status: DnssecStatus,
+}
+
+impl CloudflareClient {
+    /// Fetches all settings of the zone with their raw values.
+    ///
+    /// # Links
+    /// [see cloudflare documentation](https://developers.cloudflare.com/api/operations/zone-settings-get-all-zone-settings)
+    pub async fn get_zone_settings(&self, zone_id: &str) -> ResultResponse<ZoneSetting<serde_json::Value>> {
+        let url = format!("zones/{}/settings", zone_id);
+        let client = self.request(url.as_str(), Method::GET);
+        self.send(client).await
+    }
+
+    /// Fetches a single setting, e.g. `always_use_https` as `String`.
+    pub async fn get_zone_setting<T: DeserializeOwned>(&self, zone_id: &str, setting: &str) -> SingleResultResponse<ZoneSetting<T>> {
+        let url = format!("zones/{}/settings/{}", zone_id, setting);
+        let client = self.request(url.as_str(), Method::GET);
+        self.send(client).await
+    }
+
+    /// Changes a single setting, fails for settings that are not `editable`.
+    pub async fn update_zone_setting<T: Serialize + DeserializeOwned>(&self, zone_id: &str, setting: &str, value: &T) -> SingleResultResponse<ZoneSetting<T>> {
+        let url = format!("zones/{}/settings/{}", zone_id, setting);
+        let client = self.request(url.as_str(), Method::PATCH)
+            .json(&SettingValue { value });
+        self.send(client).await
+    }
+
+    pub async fn get_min_tls_version(&self, zone_id: &str) -> SingleResultResponse<ZoneSetting<MinTlsVersion>> {
+        self.get_zone_setting(zone_id, "min_tls_version").await
+    }
+
+    pub async fn set_min_tls_version(&self, zone_id: &str, version: MinTlsVersion) -> SingleResultResponse<ZoneSetting<MinTlsVersion>> {
+        self.update_zone_setting(zone_id, "min_tls_version", &version).await
+    }
+
+    /// # Links
+    /// [see cloudflare documentation](https://developers.cloudflare.com/api/operations/dnssec-dnssec-details)
+    pub async fn get_dnssec(&self, zone_id: &str) -> SingleResultResponse<Dnssec> {
+        let url = format!("zones/{}/dnssec", zone_id);
+        let client = self.request(url.as_str(), Method::GET);
+        self.send(client).await
+    }
+
+    /// Turns DNSSEC on or off, the status is `pending` or `pending-disabled`
+    /// until cloudflare finished.
+    ///
+    /// # Links
+    /// [see cloudflare documentation](https://developers.cloudflare.com/api/operations/dnssec-edit-dnssec-status)
+    pub async fn set_dnssec(&self, zone_id: &str, enabled: bool) -> SingleResultResponse<Dnssec> {
+        let url = format!("zones/{}/dnssec", zone_id);
+        let status = if enabled { DnssecStatus::Active } else { DnssecStatus::Disabled };
+        let client = self.request(url.as_str(), Method::PATCH)
+            .json(&DnssecUpdate { status });
+        self.send(client).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_min_tls_version() {
+        let _get = mock("GET", "/zones/1-1-1-1/settings/min_tls_version")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": true,
+                "result": {"id": "min_tls_version", "value": "1.0", "editable": true, "modified_on": null}
+            }"#)
+            .create();
+        let _update = mock("PATCH", "/zones/1-1-1-1/settings/min_tls_version")
+            .match_body(Matcher::Json(serde_json::json!({"value": "1.2"})))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": true,
+                "result": {"id": "min_tls_version", "value": "1.2", "editable": true, "modified_on": "2021-05-20T10:00:00Z"}
+            }"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let client = test_client();
+            let setting = client.get_min_tls_version("1-1-1-1").await.unwrap().result.unwrap();
+            assert_eq!(MinTlsVersion::Tls1_0, setting.value);
+            let setting = client.set_min_tls_version("1-1-1-1", MinTlsVersion::Tls1_2).await.unwrap().result.unwrap();
+            assert_eq!(MinTlsVersion::Tls1_2, setting.value);
+            assert!(setting.modified_on.is_some());
+        });
+    }
+
+    #[test]
+    fn test_dnssec() {
+        let _get = mock("GET", "/zones/1-1-1-1/dnssec")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": true,
+                "result": {
+                    "status": "active", "flags": 257, "algorithm": "13", "key_type": "ECDSAP256SHA256",
+                    "digest_type": "2", "digest_algorithm": "SHA256", "digest": "48E939042E82C22542CB377B580DFDC52A361CEFDC72E7F9107E2B6BD9306A45",
+                    "ds": "example.com. 3600 IN DS 16953 13 2 48E939042E82C22542CB377B580DFDC52A361CEFDC72E7F9107E2B6BD9306A45",
+                    "key_tag": 42, "public_key": "oXiGYrSTO+LSCJ3mohc8EP+CzF9KxBj8/ydXJ22pKuZP3VAC3/Md/k7xZfz470CoRyZJ6gV6vml07IC3d8xqhA==",
+                    "modified_on": "2021-05-20T10:00:00Z"
+                }
+            }"#)
+            .create();
+        let _disable = mock("PATCH", "/zones/1-1-1-1/dnssec")
+            .match_body(Matcher::Json(serde_json::json!({"status": "disabled"})))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "result": {"status": "pending-disabled"}}"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let client = test_client();
+            let dnssec = client.get_dnssec("1-1-1-1").await.unwrap().result.unwrap();
+            assert_eq!(DnssecStatus::Active, dnssec.status);
+            assert_eq!(Some(42), dnssec.key_tag);
+            let dnssec = client.set_dnssec("1-1-1-1", false).await.unwrap().result.unwrap();
+            assert_eq!(DnssecStatus::PendingDisabled, dnssec.status);
+        });
+    }
+}
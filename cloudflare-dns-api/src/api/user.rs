@@ -0,0 +1,89 @@
+use super::*;
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TokenStatus {
+    pub id: String,
+    pub status: String,
+    pub not_before: Option<String>,
+    pub expires_on: Option<String>,
+}
+
+impl TokenStatus {
+    pub fn is_active(&self) -> bool {
+        self.status == "active"
+    }
+}
+
+impl CloudflareClient {
+    /// Checks that the api token of this client is valid and active.
+    ///
+    /// # Links
+    /// [see cloudflare documentation](https://api.cloudflare.com/#user-api-tokens-verify-token)
+    ///
+    pub async fn verify_token(&self) -> SingleResultResponse<TokenStatus> {
+        if let Credentials::Key { .. } = self.credentials() {
+            return Err(CloudflareError::Auth(String::from("Only api tokens can be verified, not global api keys.")));
+        }
+        let client = self.request("user/tokens/verify", Method::GET);
+        self.send(client).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::mock;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_verify_token() {
+        let _m = mock("GET", "/user/tokens/verify")
+            .match_header("Authorization", "Bearer token")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": true,
+                "errors": [],
+                "messages": [{"code": 10000, "message": "This API Token is valid and active", "type": null}],
+                "result": {
+                    "id": "ed17574386854bf78a67040be0a770b0",
+                    "status": "active"
+                }
+            }"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let result = test_token_client().verify_token().await.unwrap();
+            let status = result.result.unwrap();
+            assert!(status.is_active());
+            assert_eq!("ed17574386854bf78a67040be0a770b0", status.id.as_str());
+        });
+    }
+
+    #[test]
+    fn test_verify_invalid_token() {
+        let _m = mock("GET", "/user/tokens/verify")
+            .with_status(401)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": false,
+                "errors": [{"code": 1000, "message": "Invalid API Token"}],
+                "messages": [],
+                "result": null
+            }"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = test_token_client().verify_token().await.err().unwrap();
+            assert!(matches!(error, CloudflareError::Auth(_)));
+            assert_eq!("Authentication failed: 1000: Invalid API Token", format!("{}", error));
+        });
+    }
+
+    #[test]
+    fn test_verify_global_key() {
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            assert!(test_client().verify_token().await.is_err());
+        });
+    }
+}
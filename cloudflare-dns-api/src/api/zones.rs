@@ -1,7 +1,7 @@
-use crate::{ResultResponseError};
 use super::*;
+use chrono::{DateTime, Utc};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Owner {
     pub id: Option<String>,
     pub email: Option<String>,
@@ -9,13 +9,14 @@ pub struct Owner {
     pub owner_type: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Account {
     pub id: String,
-    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Zone {
     pub id: String,
     pub name: String,
@@ -24,51 +25,118 @@ pub struct Zone {
     pub original_dnshost: Option<String>,
     pub original_name_servers: Option<Vec<String>>,
     pub name_servers: Vec<String>,
+    #[serde(default)]
     pub permissions: Vec<String>,
     pub owner: Option<Owner>,
     pub account: Option<Account>,
-    pub created_on: String,
-    pub modified_on: String,
-    pub activated_on: String,
+    pub created_on: DateTime<Utc>,
+    pub modified_on: DateTime<Utc>,
+    /// `None` until the name servers of the zone point to cloudflare
+    pub activated_on: Option<DateTime<Utc>>,
     #[serde(rename="type")]
     pub zone_type: String,
+    /// `initializing`, `pending`, `active` or `moved`
     pub status: String,
     pub paused: bool,
 }
 
-/// # Links
-/// [see cloudflare documentation](https://api.cloudflare.com/#zone-zone-details)
-///
-/// # Arguments
-/// * `name` - filter by zone-name, e.g. example.com
-///
-pub async fn get_zones(name: Option<&str>) -> ResultResponse<Zone> {
-    let mut client = cloudflare_client("zones", Method::GET);
-    if name.is_some() {
-        client = client.query(&[("name", name.unwrap())]);
+impl Zone {
+    pub fn is_active(&self) -> bool {
+        self.status == "active"
     }
-    let response = client.send().await?;
-    let response = response.json::<Response<Zone>>().await?;
-    if response.success {
-        Ok(response)
-    } else {
-        let error = ResultResponseError::from(response);
-        Result::Err(Box::from(error))
+}
+
+/// A new zone of an account, `zone_type` is `full` (default) or `partial`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ZoneCreate {
+    pub name: String,
+    pub account: Account,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub zone_type: Option<String>,
+}
+
+fn zones_query(name: Option<&str>) -> Vec<(&str, &str)> {
+    match name {
+        Some(name) => vec![("name", name)],
+        None => vec![],
+    }
+}
+
+impl CloudflareClient {
+    /// # Links
+    /// [see cloudflare documentation](https://api.cloudflare.com/#zone-zone-details)
+    ///
+    /// # Arguments
+    /// * `name` - filter by zone-name, e.g. example.com
+    ///
+    pub async fn get_zones(&self, name: Option<&str>) -> ResultResponse<Zone> {
+        let query = zones_query(name);
+        self.get_all_pages("zones", &query, self.per_page.min(PER_PAGE)).await
+    }
+
+    /// Fetches a single page of [`CloudflareClient::get_zones`], `page` starts at 1.
+    pub async fn get_zones_page(&self, name: Option<&str>, page: u32, per_page: u32) -> ResultResponse<Zone> {
+        let query = zones_query(name);
+        self.get_page("zones", &query, page, per_page).await
+    }
+
+    /// # Links
+    /// [see cloudflare documentation](https://developers.cloudflare.com/api/operations/zones-0-get)
+    pub async fn get_zone(&self, zone_id: &str) -> SingleResultResponse<Zone> {
+        let url = format!("zones/{}", zone_id);
+        let client = self.request(url.as_str(), Method::GET);
+        self.send(client).await
+    }
+
+    /// Adds the zone to the account, it stays `pending` until its name
+    /// servers point to the `name_servers` of the new zone.
+    ///
+    /// # Links
+    /// [see cloudflare documentation](https://developers.cloudflare.com/api/operations/zones-post)
+    pub async fn create_zone(&self, name: &str, account_id: &str, zone_type: Option<&str>) -> SingleResultResponse<Zone> {
+        let zone = ZoneCreate {
+            name: String::from(name),
+            account: Account { id: String::from(account_id), name: None },
+            zone_type: zone_type.map(String::from),
+        };
+        let client = self.request("zones", Method::POST).json(&zone);
+        self.send(client).await
+    }
+
+    /// Deletes the zone with all of its records.
+    ///
+    /// # Links
+    /// [see cloudflare documentation](https://developers.cloudflare.com/api/operations/zones-0-delete)
+    pub async fn delete_zone(&self, zone_id: &str) -> OnlyResultResponse<OnlyId> {
+        let url = format!("zones/{}", zone_id);
+        let client = self.request(url.as_str(), Method::DELETE);
+        self.send(client).await
+    }
+
+    /// Asks cloudflare to check the name servers of a pending zone again,
+    /// [`Zone::is_active`] of [`CloudflareClient::get_zone`] tells the result.
+    ///
+    /// # Links
+    /// [see cloudflare documentation](https://developers.cloudflare.com/api/operations/put-zones-zone_id-activation_check)
+    pub async fn check_activation(&self, zone_id: &str) -> OnlyResultResponse<OnlyId> {
+        let url = format!("zones/{}/activation_check", zone_id);
+        let client = self.request(url.as_str(), Method::PUT);
+        self.send(client).await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockito::mock;
+    use mockito::{mock, Matcher};
     use tokio::runtime::Runtime;
-    use std::env;
 
     #[test]
     fn test_get_zones_successfully() {
-        env::set_var("CLOUDFLARE_KEY", "");
-        env::set_var("CLOUDFLARE_EMAIL", "");
         let _m = mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .match_header("X-Auth-Key", "key")
+            .match_header("X-Auth-Email", "mail@example.com")
             .with_header("content-type", "application/json")
             .with_body(r#"
             {
@@ -80,9 +148,9 @@ mod tests {
                         "development_mode": 1,
                         "name_servers": ["example.ns.com"],
                         "permissions": ["read", "write"],
-                        "created_on": "20.05.2021",
-                        "modified_on": "20.05.2021",
-                        "activated_on": "20.05.2021",
+                        "created_on": "2021-05-20T10:00:00.000000Z",
+                        "modified_on": "2021-05-20T10:00:00.000000Z",
+                        "activated_on": "2021-05-20T10:00:00.000000Z",
                         "type": "full",
                         "status": "active",
                         "paused": false
@@ -93,20 +161,22 @@ mod tests {
             .create();
         let runtime = Runtime::new().expect("Init successful");
         runtime.block_on(async move {
-            let zones = get_zones(Option::None).await.unwrap();
+            let zones = test_client().get_zones(Option::None).await.unwrap();
             assert!(zones.success);
             assert!(zones.result.is_some());
             let zones = zones.result.unwrap();
             assert_eq!(1, zones.len());
             assert_eq!("example.com", zones[0].name.as_str());
+            assert!(zones[0].is_active());
         });
     }
 
     #[test]
     fn test_get_zones_status_is_false() {
-        env::set_var("CLOUDFLARE_KEY", "");
-        env::set_var("CLOUDFLARE_EMAIL", "");
         let _m = mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .match_header("X-Auth-Key", "key")
+            .match_header("X-Auth-Email", "mail@example.com")
             .with_header("content-type", "application/json")
             .with_body(r#"
             {
@@ -119,13 +189,74 @@ mod tests {
             .create();
         let runtime = Runtime::new().expect("Init successful");
         runtime.block_on(async move {
-            let result = get_zones(Option::None).await;
+            let result = test_client().get_zones(Option::None).await;
             assert!(result.is_err());
-            let error = result.err();
-            assert!(error.is_some());
-            let error: Box<ResultResponseError> = error.unwrap().downcast().unwrap();
-            assert_eq!(1, error.errors.len());
-            assert_eq!("There is an error:\n1074: Could not find a valid zone.", format!("{}", error));
+            let error = result.err().unwrap();
+            assert!(error.has_code(1074));
+            assert_eq!("Request failed with status 200:\n1074: Could not find a valid zone.", format!("{}", error));
+        });
+    }
+
+    #[test]
+    fn test_get_zones_forbidden() {
+        let _m = mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .with_status(403)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": false,
+                "errors": [{"code": 9109, "message": "Unauthorized to access requested resource"}]
+            }"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = test_client().get_zones(Option::None).await.err().unwrap();
+            assert!(matches!(error, CloudflareError::Auth(_)));
+        });
+    }
+
+    #[test]
+    fn test_find_unknown_zone() {
+        let _m = mock("GET", "/zones")
+            .match_query(Matcher::UrlEncoded(String::from("name"), String::from("example.org")))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "errors": [], "messages": [], "result": []}"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = test_client().find_zone("example.org").await.err().unwrap();
+            assert!(matches!(error, CloudflareError::ZoneNotFound(_)));
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_create_pending_zone() {
+        let _create = mock("POST", "/zones")
+            .match_body(Matcher::Json(serde_json::json!({"name": "new.example", "account": {"id": "account"}})))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "success": true,
+                "result": {
+                    "id": "3-3-3-3", "name": "new.example", "development_mode": 0,
+                    "name_servers": ["ada.ns.cloudflare.com", "bob.ns.cloudflare.com"],
+                    "account": {"id": "account", "name": "Example"},
+                    "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z", "activated_on": null,
+                    "type": "full", "status": "pending", "paused": false
+                }
+            }"#)
+            .create();
+        let _check = mock("PUT", "/zones/3-3-3-3/activation_check")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "result": {"id": "3-3-3-3"}}"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let client = test_client();
+            let zone = client.create_zone("new.example", "account", None).await.unwrap().result.unwrap();
+            assert!(!zone.is_active());
+            assert_eq!(None, zone.activated_on);
+            assert_eq!(2, zone.name_servers.len());
+            assert_eq!("3-3-3-3", client.check_activation("3-3-3-3").await.unwrap().result.id);
+        });
+    }
+}
@@ -1,118 +1,89 @@
-mod api;
+pub mod api;
+mod provider;
 
 use std::error::Error;
-use std::fmt::{Debug, Formatter};
-use api::{Response, ResponseError};
+use std::fmt::Formatter;
+use api::ResponseError;
 use api::zones::*;
-use api::records::*;
-use crate::api::SingleResult;
+pub use api::{CloudflareClient, CloudflareClientBuilder, Credentials};
 
 #[derive(Debug)]
-pub struct ResultError(String);
-
-#[derive(Debug)]
-pub struct ResultResponseError {
-    pub errors: Vec<ResponseError>
+pub enum CloudflareError {
+    /// the credentials are missing, unusable or were rejected (401/403)
+    Auth(String),
+    /// there is no zone with this name
+    ZoneNotFound(String),
+    /// too many requests (429), `retry_after` is in seconds
+    RateLimited { retry_after: Option<u64> },
+    /// the api answered with `"success": false` and these errors
+    Api { status: u16, errors: Vec<ResponseError> },
+    /// any other unsuccessful status without cloudflare errors in the body
+    Status { status: u16, message: String },
+    /// the response body could not be decoded
+    Decode(serde_json::Error),
+    /// the request could not be sent or the response could not be read
+    Transport(reqwest::Error),
 }
 
-impl std::fmt::Display for ResultError {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "There is an error: {}", self.0)
+impl CloudflareError {
+    /// Returns true if the api answered with an error of this code.
+    pub fn has_code(&self, code: i32) -> bool {
+        match self {
+            CloudflareError::Api { errors, .. } => errors.iter().any(|error| error.code == code),
+            _ => false,
+        }
     }
 }
 
-impl std::fmt::Display for ResultResponseError {
+impl std::fmt::Display for CloudflareError {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        let errors = &self.errors;
-        let errors: Vec<String> = errors.into_iter().map(|x| format!("{}: {}", x.code, x.message)).collect();
-        write!(f, "There is an error:\n{}", errors.join("\n"))
-    }
-}
-
-impl Error for ResultError {}
-
-impl Error for ResultResponseError {}
-
-impl<T> From<Response<T>> for ResultResponseError {
-    fn from(item: Response<T>) -> Self {
-        let default = ResponseError {code: 0, message: "Unknown Error.".parse().unwrap() };
-        ResultResponseError {
-            errors: item.errors.unwrap_or(vec!(default))
+        match self {
+            CloudflareError::Auth(message) => write!(f, "Authentication failed: {}", message),
+            CloudflareError::ZoneNotFound(name) => write!(f, "No Zone found for name {}.", name),
+            CloudflareError::RateLimited { retry_after: Some(seconds) } => write!(f, "Rate limited, retry after {} seconds", seconds),
+            CloudflareError::RateLimited { retry_after: None } => write!(f, "Rate limited"),
+            CloudflareError::Api { status, errors } if errors.is_empty() => write!(f, "Request failed with status {}: Unknown Error.", status),
+            CloudflareError::Api { status, errors } => {
+                let errors: Vec<String> = errors.iter().map(|x| format!("{}: {}", x.code, x.message)).collect();
+                write!(f, "Request failed with status {}:\n{}", status, errors.join("\n"))
+            }
+            CloudflareError::Status { status, message } => write!(f, "Request failed with status {}: {}", status, message),
+            CloudflareError::Decode(e) => write!(f, "Could not decode response: {}", e),
+            CloudflareError::Transport(e) => write!(f, "Request failed: {}", e),
         }
     }
 }
 
-impl<T> From<SingleResult<T>> for ResultResponseError {
-    fn from(item: SingleResult<T>) -> Self {
-        let default = ResponseError {code: 0, message: "Unknown Error.".parse().unwrap() };
-        ResultResponseError {
-            errors: item.errors.unwrap_or(vec!(default))
+impl Error for CloudflareError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CloudflareError::Decode(e) => Some(e),
+            CloudflareError::Transport(e) => Some(e),
+            _ => None,
         }
     }
 }
 
-pub async fn get_zone_by_name(name: &str) -> Result<Zone, Box<dyn Error>> {
-    let zones = api::zones::get_zones(Option::from(name)).await.unwrap();
-    let zones = match zones.result {
-        None => Vec::new(),
-        Some(t) => t
-    };
-    match zones.into_iter().next() {
-        None => Err(Box::new(ResultError(format!("No Zone found for name {}.", name)))),
-        Some(zone) => Ok(zone)
+impl From<reqwest::Error> for CloudflareError {
+    fn from(error: reqwest::Error) -> Self {
+        CloudflareError::Transport(error)
     }
 }
 
-pub async fn get_all_records_by_name(zone_name: &str) -> Result<Response<Record>, Box<dyn Error>> {
-    let zone = match get_zone_by_name(zone_name).await {
-        Ok(zone) => zone,
-        Err(e) => return Err(e)
-    };
-    Ok(get_all_records(zone.id).await?)
-}
-
-
-pub async fn delete_records_by_name(zone: &str, record_name: &str) -> Result<(), Box<dyn Error>> {
-    let data = get_all_records_by_name(&zone)
-        .await?
-        .result
-        .unwrap()
-        .into_iter()
-        .filter(|record| record.name == record_name);
-    for record in data {
-        delete_record(zone, record.id.as_str()).await;
+impl From<serde_json::Error> for CloudflareError {
+    fn from(error: serde_json::Error) -> Self {
+        CloudflareError::Decode(error)
     }
-    Ok(())
 }
 
-pub async fn create_update_record(zone_name: &str, record_name: &str, value: &str, record_type: &str) -> Result<(), Box<dyn Error>> {
-    let records = get_all_records_by_name(zone_name)
-        .await?
-        .result
-        .unwrap()
-        .into_iter()
-        .filter(|record| record.name.starts_with(record_name))
-        .collect::<Vec<_>>();
-    let count = records.len();
-    if count > 1 {
-        delete_records_by_name(zone_name, record_name).await?;
-    }
-    if count == 1 {
-        let mut records = records.iter();
-        let record = records.next().unwrap();
-        let zone = get_zone_by_name(zone_name).await?;
-        update_record(
-            record.id.as_str(),
-            zone.id,
-            Option::from(record_name),
-            Option::from(record_type),
-            Option::from(value),
-            Option::None,
-        ).await;
-    } else {
-        let zone = get_zone_by_name(zone_name).await?;
-        create_record(record_name, record_type, value, zone.id).await;
+impl CloudflareClient {
+    /// Returns the zone with the given name, or [`CloudflareError::ZoneNotFound`].
+    pub async fn find_zone(&self, name: &str) -> Result<Zone, CloudflareError> {
+        let zones = self.get_zones(Option::from(name)).await?;
+        let zones = zones.result.unwrap_or_default();
+        match zones.into_iter().next() {
+            None => Err(CloudflareError::ZoneNotFound(String::from(name))),
+            Some(zone) => Ok(zone)
+        }
     }
-    Ok(())
 }
-
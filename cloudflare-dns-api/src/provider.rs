@@ -0,0 +1,145 @@
+use async_trait::async_trait;
+use dns_provider::{relative_name, DnsProvider, Error, Record, RecordCreate, Zone};
+use crate::api::records;
+use crate::CloudflareClient;
+
+#[async_trait]
+impl DnsProvider for CloudflareClient {
+    async fn get_zone_by_name(&self, name: &str) -> Result<Zone, Error> {
+        let zone = self.find_zone(name).await?;
+        Ok(Zone { id: zone.id, name: zone.name })
+    }
+
+    async fn get_records(&self, zone: &Zone) -> Result<Vec<Record>, Error> {
+        let records = self.get_all_records(zone.id.clone()).await?;
+        Ok(records.result.unwrap_or_default().into_iter().map(|record| Record {
+            id: record.id,
+            name: relative_name(record.name.as_str(), zone.name.as_str()),
+            zone_id: record.zone_id,
+            record_type: record.record_type,
+            value: record.content,
+            ttl: Some(record.ttl),
+            proxied: record.proxied,
+        }).collect())
+    }
+
+    async fn create_record(&self, zone: &Zone, record: &RecordCreate) -> Result<(), Error> {
+        self.create_record(
+            record.name.as_str(),
+            record.record_type.as_str(),
+            record.value.as_str(),
+            record.ttl,
+            record.proxied,
+            zone.id.clone(),
+        ).await?;
+        Ok(())
+    }
+
+    /// Always replaces the record with a PUT, which unlike a PATCH is retried
+    /// after transient failures. The ttl and proxy setting `record` leaves
+    /// open are taken from the existing record.
+    async fn update_record(&self, zone: &Zone, record_id: &str, record: &RecordCreate) -> Result<(), Error> {
+        let mut ttl = record.ttl;
+        let mut proxied = record.proxied;
+        if ttl.is_none() || proxied.is_none() {
+            let existing = self.get_record(zone.id.as_str(), record_id)
+                .await?
+                .result
+                .ok_or_else(|| format!("No record found for id {}.", record_id))?;
+            ttl = ttl.or(Some(existing.ttl));
+            proxied = proxied.or(existing.proxied);
+        }
+        let update = records::RecordCreate {
+            record_type: Some(record.record_type.clone()),
+            name: Some(record.name.clone()),
+            content: Some(record.value.clone()),
+            ttl,
+            proxied,
+            ..records::RecordCreate::default()
+        };
+        self.put_record(zone.id.as_str(), record_id, &update).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, zone: &Zone, record_id: &str) -> Result<(), Error> {
+        self.delete_record(zone.id.as_str(), record_id).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{Credentials, test_client};
+    use dns_provider::RetryPolicy;
+    use mockito::{mock, Matcher};
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+
+    fn zone() -> Zone {
+        Zone { id: String::from("update-zone"), name: String::from("example.com") }
+    }
+
+    fn record(ttl: Option<u32>, proxied: Option<bool>) -> RecordCreate {
+        RecordCreate {
+            name: String::from("home"),
+            record_type: String::from("A"),
+            value: String::from("203.0.113.7"),
+            ttl,
+            proxied,
+        }
+    }
+
+    #[test]
+    fn test_update_record_retries_put() {
+        let _existing = mock("GET", "/zones/update-zone/dns_records/retried")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "result": {
+                "id": "retried", "type": "A", "name": "home.example.com", "content": "192.0.2.1",
+                "proxied": true, "ttl": 300,
+                "created_on": "2021-05-20T10:00:00Z", "modified_on": "2021-05-20T10:00:00Z"
+            }}"#)
+            .create();
+        let body = Matcher::Json(serde_json::json!({
+            "type": "A", "name": "home", "content": "203.0.113.7", "ttl": 300, "proxied": true
+        }));
+        let unavailable = mock("PUT", "/zones/update-zone/dns_records/retried")
+            .match_body(body.clone())
+            .with_status(503)
+            .expect(1)
+            .create();
+        let replaced = mock("PUT", "/zones/update-zone/dns_records/retried")
+            .match_body(body)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "result": null}"#)
+            .expect(1)
+            .create();
+        let client = CloudflareClient::builder(Credentials::Token(String::from("token")))
+            .base_url(&mockito::server_url())
+            .retry(RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::default() })
+            .build()
+            .unwrap();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            DnsProvider::update_record(&client, &zone(), "retried", &record(None, None)).await.unwrap();
+        });
+        unavailable.assert();
+        replaced.assert();
+    }
+
+    #[test]
+    fn test_update_record_with_all_fields_skips_lookup() {
+        let lookup = mock("GET", "/zones/update-zone/dns_records/complete").expect(0).create();
+        let replaced = mock("PUT", "/zones/update-zone/dns_records/complete")
+            .match_body(Matcher::PartialJson(serde_json::json!({"ttl": 60, "proxied": false})))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "result": null}"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            DnsProvider::update_record(&test_client(), &zone(), "complete", &record(Some(60), Some(false))).await.unwrap();
+        });
+        lookup.assert();
+        replaced.assert();
+    }
+}
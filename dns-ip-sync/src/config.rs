@@ -0,0 +1,512 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use dns_provider::{DuplicatePolicy, RetryPolicy};
+use crate::ip::AddressFamily;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Hetzner,
+    Cloudflare,
+}
+
+/// Where the address written into a record comes from.
+///
+/// `"local"`, `"http"`, `"dns"` and `"consensus"` select a resolver, an ip
+/// address is taken as a fixed address, anything else is rejected.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[serde(try_from = "String")]
+pub enum IpSource {
+    #[default]
+    Local,
+    Http,
+    Dns,
+    Consensus,
+    Address(IpAddr),
+}
+
+impl TryFrom<String> for IpSource {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "local" => Ok(IpSource::Local),
+            "http" => Ok(IpSource::Http),
+            "dns" => Ok(IpSource::Dns),
+            "consensus" => Ok(IpSource::Consensus),
+            _ => value.parse::<IpAddr>()
+                .map(IpSource::Address)
+                .map_err(|_| format!("Unknown ip source {}, expected local, http, dns, consensus or an ip address", value)),
+        }
+    }
+}
+
+/// Record types written for a target, `"both"` syncs A and AAAA records
+/// for the same name.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub enum RecordTypes {
+    #[default]
+    A,
+    Aaaa,
+    Both,
+}
+
+impl RecordTypes {
+    pub fn families(&self) -> Vec<AddressFamily> {
+        match self {
+            RecordTypes::A => vec![AddressFamily::V4],
+            RecordTypes::Aaaa => vec![AddressFamily::V6],
+            RecordTypes::Both => vec![AddressFamily::V4, AddressFamily::V6],
+        }
+    }
+}
+
+impl FromStr for RecordTypes {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "A" => Ok(RecordTypes::A),
+            "AAAA" => Ok(RecordTypes::Aaaa),
+            "both" => Ok(RecordTypes::Both),
+            _ => Err(format!("Unknown record type {}, expected A, AAAA or both", value)),
+        }
+    }
+}
+
+impl TryFrom<String> for RecordTypes {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Settings of the public ip resolvers, shared by all targets.
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "IpSettingsEntry")]
+pub struct IpSettings {
+    pub urls: Vec<String>,
+    pub dns_server: String,
+    pub dns_name: String,
+    /// number of sources that have to agree in consensus mode
+    pub consensus: usize,
+}
+
+impl Default for IpSettings {
+    fn default() -> Self {
+        IpSettings {
+            urls: vec![
+                String::from("https://api64.ipify.org"),
+                String::from("https://ifconfig.me/ip"),
+                String::from("https://icanhazip.com"),
+            ],
+            dns_server: String::from("resolver1.opendns.com:53"),
+            dns_name: String::from("myip.opendns.com"),
+            consensus: 2,
+        }
+    }
+}
+
+impl IpSettings {
+    /// Number of resolvers asked in consensus mode, each url and the dns query.
+    pub fn sources(&self) -> usize {
+        self.urls.len() + 1
+    }
+
+    /// Checks that at least one and at most all sources have to agree.
+    pub fn parse_consensus<T>(&self, consensus: T) -> Result<usize, String>
+        where T: TryInto<usize> + Display + Copy {
+        match consensus.try_into() {
+            Ok(required) if required >= 1 && required <= self.sources() => Ok(required),
+            _ => Err(format!("Invalid consensus {}, expected 1 to {} sources", consensus, self.sources())),
+        }
+    }
+}
+
+/// The `[ip]` settings as written in the config, checked into [`IpSettings`].
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct IpSettingsEntry {
+    urls: Option<Vec<String>>,
+    dns_server: Option<String>,
+    dns_name: Option<String>,
+    consensus: Option<i64>,
+}
+
+impl TryFrom<IpSettingsEntry> for IpSettings {
+    type Error = String;
+
+    fn try_from(entry: IpSettingsEntry) -> Result<Self, Self::Error> {
+        let defaults = IpSettings::default();
+        let mut settings = IpSettings {
+            urls: entry.urls.unwrap_or(defaults.urls),
+            dns_server: entry.dns_server.unwrap_or(defaults.dns_server),
+            dns_name: entry.dns_name.unwrap_or(defaults.dns_name),
+            consensus: defaults.consensus,
+        };
+        settings.consensus = match entry.consensus {
+            Some(consensus) => settings.parse_consensus(consensus)?,
+            None => settings.parse_consensus(settings.consensus)?,
+        };
+        Ok(settings)
+    }
+}
+
+/// Credentials of the cloudflare account, the envs `CLOUDFLARE_API_TOKEN` or
+/// `CLOUDFLARE_KEY` and `CLOUDFLARE_EMAIL` are used if nothing is set.
+#[derive(Deserialize, Debug, Default)]
+pub struct CloudflareSettings {
+    pub api_token: Option<String>,
+    pub key: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Retries of rate limited and failed provider requests.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetrySettings {
+    /// attempts of a request including the first one, 1 never retries
+    pub attempts: u32,
+    /// no retry is started later than this after the first attempt, e.g. "1m"
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub deadline: Duration,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        RetrySettings {
+            attempts: policy.max_attempts,
+            deadline: policy.deadline,
+        }
+    }
+}
+
+impl RetrySettings {
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.attempts.max(1),
+            deadline: self.deadline,
+            ..RetryPolicy::default()
+        }
+    }
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    humantime::parse_duration(value.as_str()).map_err(serde::de::Error::custom)
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "TargetEntry")]
+pub struct Target {
+    pub provider: Provider,
+    pub zone: String,
+    pub name: String,
+    pub record_type: RecordTypes,
+    pub ttl: Option<u32>,
+    /// cloudflare only, whether the traffic goes through cloudflare
+    pub proxied: Option<bool>,
+    pub ip: IpSource,
+    /// what to do if the name has several records of the type
+    pub duplicates: DuplicatePolicy,
+}
+
+/// A `[[target]]` as written in the config, checked into a [`Target`].
+#[derive(Deserialize)]
+struct TargetEntry {
+    provider: Provider,
+    zone: String,
+    name: String,
+    #[serde(rename = "type", default)]
+    record_type: RecordTypes,
+    ttl: Option<i64>,
+    proxied: Option<bool>,
+    #[serde(default)]
+    ip: IpSource,
+    #[serde(default)]
+    duplicates: DuplicatePolicy,
+}
+
+impl TryFrom<TargetEntry> for Target {
+    type Error = String;
+
+    fn try_from(entry: TargetEntry) -> Result<Self, Self::Error> {
+        let ttl = match entry.ttl {
+            Some(ttl) => Some(parse_ttl(ttl)
+                .map_err(|e| format!("{} of target {} in zone {}", e, entry.name, entry.zone))?),
+            None => None,
+        };
+        Ok(Target {
+            provider: entry.provider,
+            zone: entry.zone,
+            name: entry.name,
+            record_type: entry.record_type,
+            ttl,
+            proxied: entry.proxied,
+            ip: entry.ip,
+            duplicates: entry.duplicates,
+        })
+    }
+}
+
+/// Checks a ttl in seconds of the config or the flags, records need at least 1.
+pub fn parse_ttl<T>(ttl: T) -> Result<u32, String>
+    where T: TryInto<u32> + Display + Copy {
+    match ttl.try_into() {
+        Ok(seconds) if seconds >= 1 => Ok(seconds),
+        _ => Err(format!("Invalid ttl {}, expected 1 to {} seconds", ttl, u32::MAX)),
+    }
+}
+
+fn deserialize_ttl<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let value = i64::deserialize(deserializer)?;
+    parse_ttl(value).map_err(serde::de::Error::custom)
+}
+
+/// # Example
+/// ```toml
+/// lower_ttl = true
+///
+/// [ip]
+/// urls = ["https://api64.ipify.org", "https://icanhazip.com"]
+/// consensus = 2
+///
+/// [retry]
+/// attempts = 5
+/// deadline = "2m"
+///
+/// [cloudflare]
+/// api_token = "your-scoped-api-token"
+///
+/// [[target]]
+/// provider = "cloudflare"
+/// zone = "example.com"
+/// name = "home"
+/// ttl = 300
+/// proxied = true
+/// ip = "consensus"
+/// duplicates = "refuse"
+///
+/// [[target]]
+/// provider = "hetzner"
+/// zone = "example.org"
+/// name = "vpn"
+/// ip = "203.0.113.7"
+/// ```
+#[derive(Deserialize, Debug)]
+pub struct Config {
+    /// ttl of new records, and the ttl records are lowered to with `lower_ttl`
+    #[serde(default = "default_ttl", deserialize_with = "deserialize_ttl")]
+    pub default_ttl: u32,
+    /// lower the ttl of a record first and change its address in a later run,
+    /// after resolvers dropped the record with the old ttl
+    #[serde(default)]
+    pub lower_ttl: bool,
+    /// where changes waiting for the old ttl to expire are kept
+    #[serde(default = "default_state_file")]
+    pub state_file: String,
+    #[serde(default)]
+    pub ip: IpSettings,
+    #[serde(default)]
+    pub cloudflare: CloudflareSettings,
+    #[serde(default)]
+    pub retry: RetrySettings,
+    #[serde(rename = "target", default)]
+    pub targets: Vec<Target>,
+}
+
+pub const DEFAULT_TTL: u32 = 60;
+pub const DEFAULT_STATE_FILE: &str = "dns-ip-sync.state.toml";
+
+fn default_ttl() -> u32 {
+    DEFAULT_TTL
+}
+
+fn default_state_file() -> String {
+    String::from(DEFAULT_STATE_FILE)
+}
+
+impl Config {
+    pub fn from_file(path: &str) -> Result<Config, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        Config::from_str(content.as_str())
+    }
+
+    pub fn from_str(content: &str) -> Result<Config, Box<dyn Error>> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_targets() {
+        let config = Config::from_str(r#"
+            [[target]]
+            provider = "cloudflare"
+            zone = "example.com"
+            name = "home"
+            ttl = 300
+            proxied = false
+            duplicates = "round-robin"
+
+            [[target]]
+            provider = "hetzner"
+            zone = "example.org"
+            name = "vpn"
+            type = "AAAA"
+            ip = "2001:db8::1"
+        "#).unwrap();
+        assert_eq!(2, config.targets.len());
+        let first = &config.targets[0];
+        assert_eq!(Provider::Cloudflare, first.provider);
+        assert_eq!(RecordTypes::A, first.record_type);
+        assert_eq!(Some(300), first.ttl);
+        assert_eq!(Some(false), first.proxied);
+        assert_eq!(IpSource::Local, first.ip);
+        assert_eq!(DuplicatePolicy::RoundRobin, first.duplicates);
+        let second = &config.targets[1];
+        assert_eq!(Provider::Hetzner, second.provider);
+        assert_eq!(RecordTypes::Aaaa, second.record_type);
+        assert_eq!(None, second.ttl);
+        assert_eq!(None, second.proxied);
+        assert_eq!(IpSource::Address("2001:db8::1".parse().unwrap()), second.ip);
+        assert_eq!(DuplicatePolicy::KeepFirst, second.duplicates);
+        assert_eq!(2, config.ip.consensus);
+        assert_eq!(None, config.cloudflare.api_token);
+    }
+
+    #[test]
+    fn test_reject_unknown_ip_source() {
+        for ip in ["loacl", "https", "203.0.113.300"] {
+            let error = Config::from_str(format!(r#"
+                [[target]]
+                provider = "hetzner"
+                zone = "example.org"
+                name = "vpn"
+                ip = "{}"
+            "#, ip).as_str()).unwrap_err();
+            assert!(error.to_string().contains(format!("Unknown ip source {}", ip).as_str()), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_parse_ip_settings() {
+        let config = Config::from_str(r#"
+            default_ttl = 120
+            lower_ttl = true
+
+            [ip]
+            urls = ["https://ip.example.com"]
+            consensus = 1
+
+            [[target]]
+            provider = "hetzner"
+            zone = "example.org"
+            name = "vpn"
+            ip = "dns"
+        "#).unwrap();
+        assert_eq!(vec![String::from("https://ip.example.com")], config.ip.urls);
+        assert_eq!(1, config.ip.consensus);
+        assert_eq!("myip.opendns.com", config.ip.dns_name.as_str());
+        assert_eq!(IpSource::Dns, config.targets[0].ip);
+        assert_eq!(120, config.default_ttl);
+        assert!(config.lower_ttl);
+        assert_eq!(DEFAULT_STATE_FILE, config.state_file.as_str());
+    }
+
+    #[test]
+    fn test_parse_cloudflare_settings() {
+        let config = Config::from_str(r#"
+            [cloudflare]
+            api_token = "secret"
+        "#).unwrap();
+        assert_eq!(Some(String::from("secret")), config.cloudflare.api_token);
+        assert_eq!(0, config.targets.len());
+        assert_eq!(DEFAULT_TTL, config.default_ttl);
+        assert!(!config.lower_ttl);
+        assert_eq!(RetryPolicy::default(), config.retry.policy());
+    }
+
+    #[test]
+    fn test_parse_retry_settings() {
+        let config = Config::from_str(r#"
+            [retry]
+            attempts = 6
+            deadline = "2m 30s"
+        "#).unwrap();
+        assert_eq!(6, config.retry.policy().max_attempts);
+        assert_eq!(Duration::from_secs(150), config.retry.policy().deadline);
+        assert!(Config::from_str("[retry]\ndeadline = \"soon\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_both_record_types() {
+        let config = Config::from_str(r#"
+            [[target]]
+            provider = "hetzner"
+            zone = "example.org"
+            name = "vpn"
+            type = "both"
+        "#).unwrap();
+        assert_eq!(vec![AddressFamily::V4, AddressFamily::V6], config.targets[0].record_type.families());
+        let config = Config::from_str(r#"
+            [[target]]
+            provider = "hetzner"
+            zone = "example.org"
+            name = "vpn"
+            type = "CNAME"
+        "#);
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_reject_invalid_consensus() {
+        for consensus in &["0", "-1", "5"] {
+            let error = Config::from_str(format!("[ip]\nconsensus = {}", consensus).as_str()).unwrap_err();
+            assert!(error.to_string().contains(format!("Invalid consensus {}, expected 1 to 4 sources", consensus).as_str()), "{}", error);
+        }
+        assert!(Config::from_str("[ip]\nurls = []").is_err());
+        assert_eq!(4, Config::from_str("[ip]\nconsensus = 4").unwrap().ip.consensus);
+    }
+
+    #[test]
+    fn test_reject_ttl_below_one() {
+        for ttl in &["0", "-60"] {
+            let error = Config::from_str(format!(r#"
+                [[target]]
+                provider = "hetzner"
+                zone = "example.org"
+                name = "vpn"
+                ttl = {}
+            "#, ttl).as_str()).unwrap_err();
+            assert!(error.to_string().contains("of target vpn in zone example.org"), "{}", error);
+        }
+        assert!(Config::from_str("default_ttl = 0").is_err());
+        assert!(Config::from_str("default_ttl = 4294967296").is_err());
+        assert_eq!(Ok(1), parse_ttl(1));
+        assert!(parse_ttl(-1).is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_provider() {
+        let config = Config::from_str(r#"
+            [[target]]
+            provider = "route53"
+            zone = "example.com"
+            name = "home"
+        "#);
+        assert!(config.is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use std::error::Error;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::sleep;
+use tracing::{error, info};
+use crate::config::Config;
+use crate::output::Output;
+use crate::sync::Syncer;
+
+/// Syncs every `interval` plus a random delay of up to `jitter`, until SIGTERM
+/// or SIGINT is received. A running sync is always finished before stopping.
+///
+/// SIGHUP reloads the config with `load` and syncs right away. The result of
+/// every run is printed with `output`, messages of the daemon are logged.
+pub async fn run<F>(load: F, interval: Duration, jitter: Duration, output: Output) -> Result<(), Box<dyn Error>>
+where
+    F: Fn() -> Result<Config, Box<dyn Error>>,
+{
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut syncer = Syncer::new(load()?, false);
+    loop {
+        output.print(&syncer.run().await);
+        tokio::select! {
+            _ = sleep(interval + dns_provider::jitter(jitter)) => {}
+            _ = terminate.recv() => {
+                info!("Received SIGTERM, stopping.");
+                return Ok(());
+            }
+            _ = interrupt.recv() => {
+                info!("Received SIGINT, stopping.");
+                return Ok(());
+            }
+            _ = hangup.recv() => {
+                info!("Received SIGHUP, reloading config.");
+                match load() {
+                    Ok(config) => syncer = Syncer::new(config, false),
+                    Err(e) => error!("Could not reload config, keeping the current one: {}", e),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,476 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::Formatter;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use local_ip_address::{list_afinet_netifas, local_ip};
+use tokio::net::{lookup_host, UdpSocket};
+use tokio::time::timeout;
+use tracing::warn;
+use crate::config::{IpSettings, IpSource};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+const DNS_MAX_LABEL: usize = 63;
+const DNS_MAX_NAME: usize = 253;
+// see `IFA_F_*` in linux/if_addr.h
+const IFA_F_TEMPORARY: u8 = 0x01;
+const IFA_F_DADFAILED: u8 = 0x08;
+const IFA_F_DEPRECATED: u8 = 0x20;
+const IFA_F_TENTATIVE: u8 = 0x40;
+
+#[derive(Debug)]
+pub struct ResolveError(String);
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Could not resolve ip address: {}", self.0)
+    }
+}
+
+impl Error for ResolveError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressFamily {
+    V4,
+    V6,
+}
+
+impl AddressFamily {
+    pub fn record_type(&self) -> &'static str {
+        match self {
+            AddressFamily::V4 => "A",
+            AddressFamily::V6 => "AAAA",
+        }
+    }
+
+    fn matches(&self, address: &IpAddr) -> bool {
+        match self {
+            AddressFamily::V4 => address.is_ipv4(),
+            AddressFamily::V6 => address.is_ipv6(),
+        }
+    }
+
+    fn unspecified(&self) -> IpAddr {
+        match self {
+            AddressFamily::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            AddressFamily::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+}
+
+/// Detects the address that should be written into the dns records.
+#[derive(Debug, Clone)]
+pub enum IpResolver {
+    /// fixed address, e.g. from `--ip`
+    Fixed(IpAddr),
+    /// address of the local interface, only useful for hosts without NAT.
+    /// For IPv6 only stable global addresses are used.
+    Local,
+    /// "what is my ip" endpoints, tried in order until one answers
+    Http(Vec<String>),
+    /// dns based lookup, e.g. `myip.opendns.com` at `resolver1.opendns.com`
+    Dns { name: String, server: String },
+    /// asks every source and requires `required` of them to agree
+    Consensus { sources: Vec<IpResolver>, required: usize },
+}
+
+impl IpResolver {
+    pub fn new(source: &IpSource, settings: &IpSettings) -> IpResolver {
+        match source {
+            IpSource::Address(address) => IpResolver::Fixed(*address),
+            IpSource::Local => IpResolver::Local,
+            IpSource::Http => IpResolver::Http(settings.urls.clone()),
+            IpSource::Dns => IpResolver::Dns {
+                name: settings.dns_name.clone(),
+                server: settings.dns_server.clone(),
+            },
+            IpSource::Consensus => {
+                let mut sources: Vec<IpResolver> = settings.urls
+                    .iter()
+                    .map(|url| IpResolver::Http(vec![url.clone()]))
+                    .collect();
+                sources.push(IpResolver::new(&IpSource::Dns, settings));
+                IpResolver::Consensus { sources, required: settings.consensus }
+            }
+        }
+    }
+
+    pub async fn resolve(&self, family: AddressFamily) -> Result<IpAddr, Box<dyn Error>> {
+        match self {
+            IpResolver::Consensus { sources, required } => {
+                let mut votes: Vec<(IpAddr, usize)> = Vec::new();
+                for source in sources {
+                    match resolve_source(source, family).await {
+                        Ok(address) => match votes.iter_mut().find(|(vote, _)| *vote == address) {
+                            Some((_, count)) => *count += 1,
+                            None => votes.push((address, 1)),
+                        },
+                        Err(e) => warn!("Ip source failed: {}", e),
+                    }
+                }
+                match votes.into_iter().max_by_key(|(_, count)| *count) {
+                    Some((address, count)) if count >= *required => Ok(address),
+                    Some((address, count)) => Err(Box::new(ResolveError(format!(
+                        "only {} of {} sources agree on {}, {} required", count, sources.len(), address, required
+                    )))),
+                    None => Err(Box::new(ResolveError(String::from("no source answered")))),
+                }
+            }
+            source => resolve_source(source, family).await,
+        }
+    }
+}
+
+async fn resolve_source(source: &IpResolver, family: AddressFamily) -> Result<IpAddr, Box<dyn Error>> {
+    let address = match source {
+        IpResolver::Fixed(address) => *address,
+        IpResolver::Local => match family {
+            AddressFamily::V4 => local_ip()?,
+            AddressFamily::V6 => local_ipv6()?,
+        },
+        IpResolver::Http(urls) => resolve_http(urls, family).await?,
+        IpResolver::Dns { name, server } => resolve_dns(name, server, family).await?,
+        IpResolver::Consensus { .. } => return Err(Box::new(ResolveError(String::from("nested consensus is not supported")))),
+    };
+    if !family.matches(&address) {
+        return Err(Box::new(ResolveError(format!("{} is not usable for a {} record", address, family.record_type()))));
+    }
+    Ok(address)
+}
+
+fn local_ipv6() -> Result<IpAddr, Box<dyn Error>> {
+    // the proc file also exposes the address flags needed to skip temporary addresses
+    if let Ok(content) = fs::read_to_string("/proc/net/if_inet6") {
+        return match stable_global_ipv6(content.as_str()) {
+            Some(address) => Ok(IpAddr::V6(address)),
+            None => Err(Box::new(ResolveError(String::from("no global IPv6 address on any interface")))),
+        };
+    }
+    list_afinet_netifas()?
+        .into_iter()
+        .map(|(_, address)| address)
+        .find(|address| match address {
+            IpAddr::V6(address) => is_global_ipv6(address),
+            IpAddr::V4(_) => false,
+        })
+        .ok_or_else(|| Box::new(ResolveError(String::from("no global IPv6 address on any interface"))).into())
+}
+
+/// Parses `/proc/net/if_inet6` and returns the first global address, which is
+/// neither temporary (privacy extensions), deprecated nor still tentative.
+fn stable_global_ipv6(content: &str) -> Option<Ipv6Addr> {
+    content.lines().find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.len() < 5 || columns[0].len() != 32 {
+            return None;
+        }
+        let flags = u8::from_str_radix(columns[4], 16).ok()?;
+        if flags & (IFA_F_TEMPORARY | IFA_F_DADFAILED | IFA_F_DEPRECATED | IFA_F_TENTATIVE) != 0 {
+            return None;
+        }
+        let address = Ipv6Addr::from(u128::from_str_radix(columns[0], 16).ok()?);
+        Some(address).filter(is_global_ipv6)
+    })
+}
+
+/// Skips loopback, link-local (fe80::/10) and unique local (fc00::/7) addresses.
+fn is_global_ipv6(address: &Ipv6Addr) -> bool {
+    let first = address.segments()[0];
+    !address.is_loopback()
+        && !address.is_unspecified()
+        && !address.is_multicast()
+        && first & 0xffc0 != 0xfe80
+        && first & 0xfe00 != 0xfc00
+}
+
+async fn resolve_http(urls: &[String], family: AddressFamily) -> Result<IpAddr, Box<dyn Error>> {
+    // binding to the unspecified address forces the connection over the requested family
+    let client = reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .local_address(family.unspecified())
+        .build()?;
+    let mut errors = Vec::new();
+    for url in urls {
+        let response = match client.get(url.as_str()).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                errors.push(format!("{}: {}", url, e));
+                continue;
+            }
+        };
+        match response.text().await.map(|text| text.trim().parse::<IpAddr>()) {
+            Ok(Ok(address)) => return Ok(address),
+            Ok(Err(e)) => errors.push(format!("{}: {}", url, e)),
+            Err(e) => errors.push(format!("{}: {}", url, e)),
+        }
+    }
+    if errors.is_empty() {
+        errors.push(String::from("no url configured"));
+    }
+    Err(Box::new(ResolveError(errors.join(", "))))
+}
+
+async fn resolve_dns(name: &str, server: &str, family: AddressFamily) -> Result<IpAddr, Box<dyn Error>> {
+    // resolvers like opendns answer with the address the query was sent from,
+    // so the server has to be reached over the requested family
+    let server: SocketAddr = match lookup_host(server).await?.find(|address| family.matches(&address.ip())) {
+        Some(address) => address,
+        None => return Err(Box::new(ResolveError(format!("no {} address for dns server {}", family.record_type(), server)))),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(family.unspecified(), 0)).await?;
+    socket.connect(server).await?;
+    // an unpredictable id makes spoofed answers harder to slip in
+    let id = random_id();
+    let query_type = match family {
+        AddressFamily::V4 => DNS_TYPE_A,
+        AddressFamily::V6 => DNS_TYPE_AAAA,
+    };
+    socket.send(build_query(id, name, query_type)?.as_slice()).await?;
+    let mut buffer = [0u8; 512];
+    let length = timeout(DNS_TIMEOUT, socket.recv(&mut buffer)).await??;
+    parse_response(id, name, query_type, &buffer[..length])
+}
+
+fn random_id() -> u16 {
+    let bytes = RandomState::new().build_hasher().finish().to_be_bytes();
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn build_query(id: u16, name: &str, query_type: u16) -> Result<Vec<u8>, ResolveError> {
+    let name = name.trim_end_matches('.');
+    if name.len() > DNS_MAX_NAME {
+        return Err(ResolveError(format!("dns name {} is longer than {} bytes", name, DNS_MAX_NAME)));
+    }
+    let mut query = Vec::with_capacity(18 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // recursion desired, one question
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in name.split('.') {
+        let length = match u8::try_from(label.len()) {
+            Ok(length) if length >= 1 && label.len() <= DNS_MAX_LABEL => length,
+            _ => return Err(ResolveError(format!("invalid label {:?} in dns name {}, expected 1 to {} bytes", label, name, DNS_MAX_LABEL))),
+        };
+        query.push(length);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&query_type.to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes());
+    Ok(query)
+}
+
+fn parse_response(id: u16, name: &str, query_type: u16, packet: &[u8]) -> Result<IpAddr, Box<dyn Error>> {
+    let name = name.trim_end_matches('.');
+    if read_u16(packet, 0)? != id {
+        return Err(Box::new(ResolveError(String::from("unexpected dns response id"))));
+    }
+    let flags = read_u16(packet, 2)?;
+    if flags & 0x8000 == 0 {
+        return Err(Box::new(ResolveError(String::from("dns packet is not a response"))));
+    }
+    let code = flags & 0x000f;
+    if code != 0 {
+        return Err(Box::new(ResolveError(format!("dns server answered with code {}", code))));
+    }
+    let questions = read_u16(packet, 4)?;
+    let answers = read_u16(packet, 6)?;
+    if questions != 1 {
+        return Err(Box::new(ResolveError(format!("dns response has {} questions, expected 1", questions))));
+    }
+    let (question, mut offset) = read_name(packet, 12)?;
+    if !question.eq_ignore_ascii_case(name) || read_u16(packet, offset)? != query_type {
+        return Err(Box::new(ResolveError(format!("dns response answers {} instead of {}", question, name))));
+    }
+    offset += 4;
+    for _ in 0..answers {
+        let (answer, next) = read_name(packet, offset)?;
+        offset = next;
+        let record_type = read_u16(packet, offset)?;
+        let length = read_u16(packet, offset + 8)? as usize;
+        let data = packet.get(offset + 10..offset + 10 + length).ok_or_else(truncated)?;
+        offset += 10 + length;
+        if record_type != query_type || !answer.eq_ignore_ascii_case(name) {
+            continue;
+        }
+        match (record_type, data.len()) {
+            (DNS_TYPE_A, 4) => return Ok(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+            (DNS_TYPE_AAAA, 16) => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                return Ok(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            _ => continue,
+        }
+    }
+    Err(Box::new(ResolveError(String::from("no address in dns response"))))
+}
+
+/// Reads the possibly compressed name at `offset`, returns it without the
+/// trailing dot and the offset after it.
+fn read_name(packet: &[u8], mut offset: usize) -> Result<(String, usize), ResolveError> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // every pointer has to point backwards, which also ends pointer loops
+    let mut limit = offset;
+    loop {
+        let length = *packet.get(offset).ok_or_else(truncated)? as usize;
+        if length == 0 {
+            let end = end.unwrap_or(offset + 1);
+            return Ok((labels.join("."), end));
+        }
+        if length & 0xc0 == 0xc0 {
+            let pointer = usize::from(read_u16(packet, offset)? & 0x3fff);
+            if pointer >= limit {
+                return Err(ResolveError(String::from("invalid name pointer in dns response")));
+            }
+            end.get_or_insert(offset + 2);
+            limit = pointer;
+            offset = pointer;
+            continue;
+        }
+        let label = packet.get(offset + 1..offset + 1 + length).ok_or_else(truncated)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        offset += 1 + length;
+    }
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Result<u16, ResolveError> {
+    packet
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(truncated)
+}
+
+fn truncated() -> ResolveError {
+    ResolveError(String::from("truncated dns response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::Runtime;
+
+    fn response(id: u16, answer_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut packet = build_query(id, "myip.opendns.com", answer_type).unwrap();
+        // response flag, one answer
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 0x01;
+        packet.extend_from_slice(&[0xc0, 0x0c]);
+        packet.extend_from_slice(&answer_type.to_be_bytes());
+        packet.extend_from_slice(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn test_build_query() {
+        let query = build_query(0x1234, "myip.opendns.com.", DNS_TYPE_A).unwrap();
+        assert_eq!(&[0x12, 0x34], &query[0..2]);
+        assert_eq!(&[4, b'm', b'y', b'i', b'p', 7], &query[12..18]);
+        assert_eq!(&[0x00, 0x00, 0x01, 0x00, 0x01], &query[query.len() - 5..]);
+    }
+
+    #[test]
+    fn test_build_query_rejects_long_names() {
+        let label = "a".repeat(64);
+        assert!(build_query(1, format!("{}.example.com", label).as_str(), DNS_TYPE_A).is_err());
+        assert!(build_query(1, format!("{}.example.com", &label[1..]).as_str(), DNS_TYPE_A).is_ok());
+        assert!(build_query(1, "myip..opendns.com", DNS_TYPE_A).is_err());
+        let name = vec!["a".repeat(63); 4].join(".");
+        assert_eq!(255, name.len());
+        assert!(build_query(1, name.as_str(), DNS_TYPE_A).is_err());
+    }
+
+    #[test]
+    fn test_parse_a_response() {
+        let packet = response(7, DNS_TYPE_A, &[203, 0, 113, 7]);
+        let address = parse_response(7, "myip.opendns.com", DNS_TYPE_A, packet.as_slice()).unwrap();
+        assert_eq!("203.0.113.7", address.to_string());
+    }
+
+    #[test]
+    fn test_parse_aaaa_response() {
+        let address: Ipv6Addr = "2001:db8::7".parse().unwrap();
+        let packet = response(7, DNS_TYPE_AAAA, &address.octets());
+        assert_eq!(IpAddr::V6(address), parse_response(7, "myip.opendns.com", DNS_TYPE_AAAA, packet.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_stable_global_ipv6() {
+        let content = "\
+00000000000000000000000000000001 01 80 10 80       lo
+fe800000000000000000000000000001 02 40 20 80     eth0
+fd000000000000000000000000000001 02 40 00 80     eth0
+20010db8000000000000000000000002 02 40 00 01     eth0
+20010db8000000000000000000000003 02 40 00 20     eth0
+20010db8000000000000000000000001 02 40 00 00     eth0
+";
+        let address = stable_global_ipv6(content).unwrap();
+        assert_eq!("2001:db8::1", address.to_string());
+        assert_eq!(None, stable_global_ipv6(content.lines().take(4).collect::<Vec<_>>().join("\n").as_str()));
+    }
+
+    #[test]
+    fn test_parse_response_with_wrong_id() {
+        let packet = response(7, DNS_TYPE_A, &[203, 0, 113, 7]);
+        assert!(parse_response(8, "myip.opendns.com", DNS_TYPE_A, packet.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_parse_response_checks_question() {
+        let mut packet = response(7, DNS_TYPE_A, &[203, 0, 113, 7]);
+        assert!(parse_response(7, "other.opendns.com", DNS_TYPE_A, packet.as_slice()).is_err());
+        assert!(parse_response(7, "myip.opendns.com", DNS_TYPE_AAAA, packet.as_slice()).is_err());
+        assert!(parse_response(7, "MYIP.opendns.com.", DNS_TYPE_A, packet.as_slice()).is_ok());
+        // a query echoed back is not an answer
+        packet[2] &= 0x7f;
+        assert!(parse_response(7, "myip.opendns.com", DNS_TYPE_A, packet.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_parse_response_rejects_pointer_loops() {
+        let mut packet = response(7, DNS_TYPE_A, &[203, 0, 113, 7]);
+        let answer = packet.len() - 16;
+        packet[answer + 1] = u8::try_from(answer).unwrap();
+        assert!(parse_response(7, "myip.opendns.com", DNS_TYPE_A, packet.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_parse_truncated_response() {
+        let packet = response(7, DNS_TYPE_A, &[203, 0, 113, 7]);
+        assert!(parse_response(7, "myip.opendns.com", DNS_TYPE_A, &packet[..packet.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_consensus_requires_agreement() {
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let agreeing = IpResolver::Consensus {
+                sources: vec![
+                    IpResolver::Fixed("203.0.113.7".parse().unwrap()),
+                    IpResolver::Fixed("203.0.113.7".parse().unwrap()),
+                    IpResolver::Fixed("198.51.100.1".parse().unwrap()),
+                ],
+                required: 2,
+            };
+            assert_eq!("203.0.113.7", agreeing.resolve(AddressFamily::V4).await.unwrap().to_string());
+            assert!(agreeing.resolve(AddressFamily::V6).await.is_err());
+            let disagreeing = IpResolver::Consensus {
+                sources: vec![
+                    IpResolver::Fixed("203.0.113.7".parse().unwrap()),
+                    IpResolver::Fixed("198.51.100.1".parse().unwrap()),
+                ],
+                required: 2,
+            };
+            assert!(disagreeing.resolve(AddressFamily::V4).await.is_err());
+        });
+    }
+}
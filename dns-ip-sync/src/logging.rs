@@ -0,0 +1,96 @@
+use std::env;
+use tracing_subscriber::EnvFilter;
+
+/// Crates whose log level `-v` and `-vv` raise, all others only log warnings.
+const CRATES: [&str; 4] = ["dns_ip_sync", "dns_provider", "hetzner_dns_api", "cloudflare_dns_api"];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct LogSettings {
+    /// `-v` logs every http request, `-vv` also its headers with redacted credentials
+    pub verbosity: usize,
+    /// one json object per log line
+    pub json: bool,
+}
+
+/// Takes `-v`, `-vv`, `--verbose` and `--log-format` out of the args, as
+/// seahorse can not parse repeated short flags.
+pub fn parse_args(args: Vec<String>) -> Result<(Vec<String>, LogSettings), String> {
+    let mut settings = LogSettings::default();
+    let mut remaining = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let format = if arg == "--log-format" {
+            Some(args.next().unwrap_or_default())
+        } else {
+            arg.strip_prefix("--log-format=").map(String::from)
+        };
+        match format.as_deref() {
+            Some("json") => settings.json = true,
+            Some("text") => settings.json = false,
+            Some(format) => return Err(format!("Unknown log format {}, expected text or json", format)),
+            None if arg == "--verbose" => settings.verbosity += 1,
+            None if arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| c == 'v') => {
+                settings.verbosity += arg.len() - 1;
+            }
+            None => remaining.push(arg),
+        }
+    }
+    Ok((remaining, settings))
+}
+
+/// Logs to stderr, `RUST_LOG` replaces the levels of `settings` if it is set.
+pub fn init(settings: &LogSettings) {
+    let filter = match env::var("RUST_LOG") {
+        Ok(directives) if !directives.is_empty() => EnvFilter::new(directives),
+        _ => EnvFilter::new(directives(settings.verbosity)),
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    if settings.json {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+}
+
+fn directives(verbosity: usize) -> String {
+    let level = match verbosity {
+        0 => "info",
+        1 => "debug",
+        _ => "trace",
+    };
+    let crates: Vec<String> = CRATES.iter().map(|name| format!("{}={}", name, level)).collect();
+    format!("warn,{}", crates.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let (remaining, settings) = parse_args(args(&["dns-ip-sync", "-vv", "--zone=example.com", "--log-format", "json"])).unwrap();
+        assert_eq!(args(&["dns-ip-sync", "--zone=example.com"]), remaining);
+        assert_eq!(LogSettings { verbosity: 2, json: true }, settings);
+
+        let (remaining, settings) = parse_args(args(&["dns-ip-sync", "daemon", "-v", "--verbose", "--log-format=text", "-"])).unwrap();
+        assert_eq!(args(&["dns-ip-sync", "daemon", "-"]), remaining);
+        assert_eq!(LogSettings { verbosity: 2, json: false }, settings);
+
+        assert!(parse_args(args(&["dns-ip-sync", "--log-format=xml"])).is_err());
+    }
+
+    #[test]
+    fn test_directives() {
+        assert_eq!(
+            "warn,dns_ip_sync=info,dns_provider=info,hetzner_dns_api=info,cloudflare_dns_api=info",
+            directives(0)
+        );
+        assert!(directives(2).contains("hetzner_dns_api=trace"));
+    }
+}
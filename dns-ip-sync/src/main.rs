@@ -1,90 +1,376 @@
+mod config;
+mod daemon;
+mod ip;
+mod logging;
+mod output;
+mod state;
+mod sync;
+
+use std::convert::TryFrom;
 use std::env;
-use local_ip_address::local_ip;
-use seahorse::{App, Context, Flag, FlagType};
+use std::error::Error;
+use std::process;
+use std::time::Duration;
+use seahorse::{App, Command, Context, Flag, FlagType};
+use seahorse::error::FlagError;
 use tokio::runtime::Runtime;
+use tracing::error;
+use dns_provider::DuplicatePolicy;
+use config::{CloudflareSettings, Config, DEFAULT_STATE_FILE, DEFAULT_TTL, IpSettings, IpSource, Provider, RecordTypes, RetrySettings, Target};
+use output::Output;
+use sync::{Summary, Syncer};
 
-/**
-
-todo:
- - add support for toml to sync ip to multiple domains at the same time
+/// every record was already up to date
+const EXIT_UNCHANGED: i32 = 0;
+/// unexpected errors, e.g. the daemon could not listen for signals
+const EXIT_ERROR: i32 = 1;
+/// records were changed, or with `--dry-run` would be changed
+const EXIT_CHANGED: i32 = 2;
+/// some records were synced, others failed
+const EXIT_PARTIAL_FAILURE: i32 = 3;
+/// the flags, envs or config file are invalid
+const EXIT_CONFIG_ERROR: i32 = 4;
+/// every record failed, at least one because the credentials are missing or were rejected
+const EXIT_AUTH_ERROR: i32 = 5;
+/// every record failed because of the provider or the address detection
+const EXIT_PROVIDER_ERROR: i32 = 6;
 
- **/
+/// Flags of a single target and its settings, the config file declares
+/// these instead, so they are rejected together with `--config`.
+const TARGET_FLAGS: [&str; 16] = [
+    "cloudflare-dns", "ip", "ip-source", "ip-url", "consensus", "type", "zone", "domain", "ttl",
+    "proxied", "no-proxied", "lower-ttl", "state-file", "duplicates", "retries", "retry-deadline",
+];
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
+    let (args, log_settings) = match logging::parse_args(env::args().collect()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(EXIT_CONFIG_ERROR);
+        }
+    };
+    logging::init(&log_settings);
     let app = App::new(env!("CARGO_PKG_NAME"))
         .description(env!("CARGO_PKG_DESCRIPTION"))
         .author(env!("CARGO_PKG_AUTHORS"))
         .version(env!("CARGO_PKG_VERSION"))
-        .usage("cli [--cloudflare-dns] [--ip=127.0.0.1] [--zone=your-zone] [--domain=your-domain]")
+        .usage("cli [-v | -vv] [--log-format=text|json] [--dry-run] [--output=text|json|table] [--config=sync.toml | [--cloudflare-dns] [--ip=127.0.0.1 | --ip-source=local|http|dns|consensus] [--type=A|AAAA|both] [--ttl=300] [--proxied | --no-proxied] [--lower-ttl] [--zone=your-zone] [--domain=your-domain] [--duplicates=keep-first|refuse|round-robin] [--retries=4]]")
         .flag(
-            Flag::new("cloudflare-dns", FlagType::Bool)
-                .description("Use cloudflare instead of hetzner")
-        ).
-        flag(
-            Flag::new("ip", FlagType::String)
-                .description("Use provided ip address instead of using ip of localhost")
+            Flag::new("dry-run", FlagType::Bool)
+                .description("Only print the planned changes, exits with 2 if there are any")
         )
+        .action(command);
+    let daemon = Command::new("daemon")
+        .description("Stay resident and sync on a schedule")
+        .usage("cli daemon [--interval=5m] [--jitter=30s] [sync flags]")
         .flag(
-            Flag::new("zone", FlagType::String)
-                .description("Use provided zone instead of env DNS_ZONE")
+            Flag::new("interval", FlagType::String)
+                .description("Time between two syncs, e.g. 30s, 5m or 1h (default: 5m)")
         )
         .flag(
-            Flag::new("domain", FlagType::String)
-                .description("Use provided domain instead of env DNS_DOMAIN")
+            Flag::new("jitter", FlagType::String)
+                .description("Maximal random delay added to the interval (default: a tenth of the interval)")
         )
-        .action(command);
+        .action(daemon);
+    let (app, daemon) = sync_flags()
+        .into_iter()
+        .fold((app, daemon), |(app, daemon), flag| (app.flag(flag.clone()), daemon.flag(flag)));
 
-    app.run(args);
+    app.command(daemon).run(args);
     Ok(())
 }
 
+fn sync_flags() -> Vec<Flag> {
+    vec![
+        Flag::new("config", FlagType::String)
+            .description("Sync all targets declared in the provided toml file, can't be combined with the flags of a single target"),
+        // only for the help, logging::parse_args takes these out of the args
+        Flag::new("verbose", FlagType::Bool)
+            .alias("v")
+            .description("Log every http request, -vv also logs the headers with redacted credentials"),
+        Flag::new("log-format", FlagType::String)
+            .description("Log lines as text or json (default: text), RUST_LOG overrides the levels"),
+        Flag::new("output", FlagType::String)
+            .description("Print the result as text, json or table (default: text)"),
+        Flag::new("cloudflare-dns", FlagType::Bool)
+            .description("Use cloudflare instead of hetzner"),
+        Flag::new("ip", FlagType::String)
+            .description("Use provided ip address instead of using ip of localhost"),
+        Flag::new("ip-source", FlagType::String)
+            .description("Detect the ip address with local, http, dns or consensus (default: local)"),
+        Flag::new("ip-url", FlagType::String)
+            .description("Comma separated urls used by the http and consensus ip sources"),
+        Flag::new("consensus", FlagType::Int)
+            .description("Number of sources that have to agree in consensus mode (default: 2)"),
+        Flag::new("type", FlagType::String)
+            .description("Sync A, AAAA or both records (default: A)"),
+        Flag::new("zone", FlagType::String)
+            .description("Use provided zone instead of env DNS_ZONE"),
+        Flag::new("domain", FlagType::String)
+            .description("Use provided domain instead of env DNS_DOMAIN"),
+        Flag::new("ttl", FlagType::Int)
+            .description("Ttl of the record in seconds (default: keep the current ttl)"),
+        Flag::new("proxied", FlagType::Bool)
+            .description("Proxy the traffic through cloudflare"),
+        Flag::new("no-proxied", FlagType::Bool)
+            .description("Don't proxy the traffic through cloudflare"),
+        Flag::new("lower-ttl", FlagType::Bool)
+            .description("Lower the ttl first and change the address in a later run, after the old ttl expired"),
+        Flag::new("state-file", FlagType::String)
+            .description("Where --lower-ttl keeps the pending changes (default: dns-ip-sync.state.toml)"),
+        Flag::new("duplicates", FlagType::String)
+            .description("Handle several records of a name with keep-first, refuse or round-robin (default: keep-first)"),
+        Flag::new("retries", FlagType::Int)
+            .description("Attempts of a rate limited or failed provider request, 1 never retries (default: 4)"),
+        Flag::new("retry-deadline", FlagType::String)
+            .description("Don't retry a request later than this after its first attempt (default: 1m)"),
+    ]
+}
+
 fn command(context: &Context) {
+    let config = match config_from_flags(context) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(EXIT_CONFIG_ERROR);
+        }
+    };
+    let output = match output_from_flags(context) {
+        Ok(output) => output,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(EXIT_CONFIG_ERROR);
+        }
+    };
+    let dry_run = context.bool_flag("dry-run");
     let runtime = Runtime::new().expect("Init successful");
-    runtime.block_on(async move {
-        let my_local_ip = if context.string_flag("ip").is_ok() {
-            context.string_flag("ip").unwrap()
-        } else {
-            local_ip().unwrap().to_string()
-        };
-        let zone = if context.string_flag("zone").is_ok() {
-            context.string_flag("zone").unwrap()
-        } else {
-            env::var("DNS_ZONE").unwrap()
-        };
-        let domain = if context.string_flag("domain").is_ok() {
-            context.string_flag("domain").unwrap()
-        } else {
-            env::var("DNS_DOMAIN").unwrap()
-        };
-        if context.bool_flag("cloudflare-dns") {
-            update_cloudflare_ip_record(zone.as_str(), domain.as_str(), my_local_ip.as_str()).await;
+    let summary = runtime.block_on(async move {
+        Syncer::new(config, dry_run).run().await
+    });
+    output.print(&summary);
+    process::exit(exit_code(&summary));
+}
+
+fn exit_code(summary: &Summary) -> i32 {
+    if summary.failed == 0 {
+        if summary.changed + summary.pending > 0 {
+            EXIT_CHANGED
         } else {
-            update_hetzner_ip_record(zone.as_str(), domain.as_str(), my_local_ip.as_str()).await;
+            EXIT_UNCHANGED
         }
-    });
+    } else if summary.failed < summary.targets.len() {
+        EXIT_PARTIAL_FAILURE
+    } else if summary.targets.iter().any(|report| report.auth_failed) {
+        EXIT_AUTH_ERROR
+    } else {
+        EXIT_PROVIDER_ERROR
+    }
 }
 
-async fn update_cloudflare_ip_record(zone: &str, domain: &str, ip_address: &str) -> () {
-    cloudflare_dns_api::create_update_record(zone, domain, ip_address, "A").await;
-    let response = cloudflare_dns_api::get_all_records_by_name(zone).await;
-    let response = match response {
-        Ok(r) => r,
-        Err(e) => panic!("{:?}", e)
+fn daemon(context: &Context) {
+    let interval = match context.string_flag("interval").map(|value| humantime::parse_duration(value.as_str())) {
+        Ok(Ok(interval)) => interval,
+        Ok(Err(e)) => {
+            error!("Invalid interval: {}", e);
+            process::exit(EXIT_CONFIG_ERROR);
+        }
+        Err(_) => Duration::from_secs(300),
+    };
+    let jitter = match context.string_flag("jitter").map(|value| humantime::parse_duration(value.as_str())) {
+        Ok(Ok(jitter)) => jitter,
+        Ok(Err(e)) => {
+            error!("Invalid jitter: {}", e);
+            process::exit(EXIT_CONFIG_ERROR);
+        }
+        Err(_) => interval / 10,
     };
-    for record in response.result.unwrap() {
-        if record.record_type == String::from("A") {
-            println!("{:?}", record);
+    let output = match output_from_flags(context) {
+        Ok(output) => output,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(EXIT_CONFIG_ERROR);
+        }
+    };
+    if let Err(e) = config_from_flags(context) {
+        error!("{}", e);
+        process::exit(EXIT_CONFIG_ERROR);
+    }
+    let runtime = Runtime::new().expect("Init successful");
+    let result = runtime.block_on(async move {
+        daemon::run(|| config_from_flags(context), interval, jitter, output).await
+    });
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(EXIT_ERROR);
+    }
+}
+
+fn output_from_flags(context: &Context) -> Result<Output, String> {
+    match context.string_flag("output") {
+        Ok(value) => value.parse::<Output>(),
+        Err(_) => Ok(Output::Text),
+    }
+}
+
+/// Reads the config file of `--config`, or builds a config with a single
+/// target from the other flags and the envs `DNS_ZONE` and `DNS_DOMAIN`.
+fn config_from_flags(context: &Context) -> Result<Config, Box<dyn Error>> {
+    if let Ok(path) = context.string_flag("config") {
+        let target_flags: Vec<String> = TARGET_FLAGS.iter()
+            .filter(|name| is_set(context, name))
+            .map(|name| format!("--{}", name))
+            .collect();
+        if !target_flags.is_empty() {
+            return Err(Box::from(format!("{} can't be combined with --config, set them in the config file instead", target_flags.join(", "))));
         }
+        return Config::from_file(path.as_str())
+            .map_err(|e| Box::from(format!("Could not read config {}: {}", path, e)));
+    }
+    let source = if let Ok(address) = context.string_flag("ip") {
+        let address = address.parse().map_err(|_| format!("Invalid ip address {}", address))?;
+        IpSource::Address(address)
+    } else {
+        match context.string_flag("ip-source").map(IpSource::try_from) {
+            Ok(Ok(IpSource::Address(address))) => return Err(Box::from(format!("Unknown ip source {}, use --ip for a fixed address", address))),
+            Ok(source) => source?,
+            Err(_) => IpSource::Local,
+        }
+    };
+    let mut settings = IpSettings::default();
+    if let Ok(urls) = context.string_flag("ip-url") {
+        settings.urls = urls.split(',').map(|url| String::from(url.trim())).collect();
+    }
+    if let Ok(consensus) = context.int_flag("consensus") {
+        settings.consensus = settings.parse_consensus(consensus)?;
+    }
+    let record_types = match context.string_flag("type") {
+        Ok(value) => value.parse::<RecordTypes>()?,
+        Err(_) => RecordTypes::A,
+    };
+    let zone = match context.string_flag("zone") {
+        Ok(zone) => zone,
+        Err(_) => env::var("DNS_ZONE").map_err(|_| "Neither --zone nor env DNS_ZONE is set")?,
+    };
+    let domain = match context.string_flag("domain") {
+        Ok(domain) => domain,
+        Err(_) => env::var("DNS_DOMAIN").map_err(|_| "Neither --domain nor env DNS_DOMAIN is set")?,
+    };
+    let ttl = match context.int_flag("ttl") {
+        Ok(ttl) => Some(config::parse_ttl(ttl).map_err(|e| format!("{} of target {} in zone {}", e, domain, zone))?),
+        Err(_) => None,
+    };
+    let proxied = match (context.bool_flag("proxied"), context.bool_flag("no-proxied")) {
+        (true, true) => return Err(Box::from("Only one of --proxied and --no-proxied can be set")),
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        (false, false) => None,
+    };
+    let duplicates = match context.string_flag("duplicates") {
+        Ok(value) => value.parse::<DuplicatePolicy>()?,
+        Err(_) => DuplicatePolicy::KeepFirst,
+    };
+    let mut retry = RetrySettings::default();
+    if let Ok(attempts) = context.int_flag("retries") {
+        retry.attempts = u32::try_from(attempts).map_err(|_| format!("Invalid retries {}", attempts))?;
     }
+    if let Ok(deadline) = context.string_flag("retry-deadline") {
+        retry.deadline = humantime::parse_duration(deadline.as_str())
+            .map_err(|e| format!("Invalid retry deadline: {}", e))?;
+    }
+    let provider = if context.bool_flag("cloudflare-dns") {
+        Provider::Cloudflare
+    } else {
+        Provider::Hetzner
+    };
+    Ok(Config {
+        default_ttl: DEFAULT_TTL,
+        lower_ttl: context.bool_flag("lower-ttl"),
+        state_file: context.string_flag("state-file").unwrap_or_else(|_| String::from(DEFAULT_STATE_FILE)),
+        ip: settings,
+        cloudflare: CloudflareSettings::default(),
+        retry,
+        targets: vec![Target {
+            provider,
+            zone,
+            name: domain,
+            record_type: record_types,
+            ttl,
+            proxied,
+            ip: source,
+            duplicates,
+        }],
+    })
+}
+
+/// Returns true if the flag was passed, whatever its type and value.
+fn is_set(context: &Context, name: &str) -> bool {
+    !matches!(context.string_flag(name), Err(FlagError::NotFound) | Err(FlagError::Undefined))
 }
 
-async fn update_hetzner_ip_record(zone: &str, domain: &str, ip_address: &str) -> () {
-    hetzner_dns_api::create_update_record(zone, domain, ip_address, "A").await;
-    let response = hetzner_dns_api::get_all_records_by_name(zone).await;
-    for record in response.records {
-        if record.record_type == String::from("A") {
-            println!("{:?}", record);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sync::{Report, Status};
+
+    fn summary(actions: Vec<(Status, bool)>) -> Summary {
+        let mut summary = Summary::default();
+        for (action, auth_failed) in actions {
+            match action {
+                Status::Created | Status::Updated => summary.changed += 1,
+                Status::Unchanged => summary.unchanged += 1,
+                Status::Pending => summary.pending += 1,
+                Status::Failed => summary.failed += 1,
+            }
+            summary.targets.push(Report {
+                provider: Provider::Hetzner,
+                zone: String::from("example.com"),
+                name: String::from("home"),
+                record_type: String::from("A"),
+                old_value: None,
+                new_value: None,
+                action,
+                pending_until: None,
+                error: None,
+                auth_failed,
+                plan: None,
+            });
         }
+        summary
+    }
+
+    fn context(args: &[&str]) -> Context {
+        let args = args.iter().map(|arg| String::from(*arg)).collect();
+        Context::new(args, Some(sync_flags()), String::new())
+    }
+
+    #[test]
+    fn test_config_rejects_target_flags() {
+        let error = config_from_flags(&context(&["--config", "sync.toml", "--zone", "example.com", "--ttl", "300", "--proxied"])).unwrap_err();
+        assert_eq!("--zone, --ttl, --proxied can't be combined with --config, set them in the config file instead", error.to_string());
+
+        let error = config_from_flags(&context(&["--config", "missing.toml", "--output", "json"])).unwrap_err();
+        assert!(error.to_string().starts_with("Could not read config missing.toml"));
+    }
+
+    #[test]
+    fn test_flags_reject_invalid_consensus() {
+        for consensus in &["0", "-1"] {
+            let error = config_from_flags(&context(&["--zone", "example.com", "--domain", "home", "--consensus", consensus])).unwrap_err();
+            assert_eq!(format!("Invalid consensus {}, expected 1 to 4 sources", consensus), error.to_string());
+        }
+        let config = config_from_flags(&context(&["--zone", "example.com", "--domain", "home", "--ip-url", "https://ip.example.com", "--consensus", "2"])).unwrap();
+        assert_eq!(2, config.ip.consensus);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(EXIT_UNCHANGED, exit_code(&summary(vec![])));
+        assert_eq!(EXIT_UNCHANGED, exit_code(&summary(vec![(Status::Unchanged, false)])));
+        assert_eq!(EXIT_CHANGED, exit_code(&summary(vec![(Status::Unchanged, false), (Status::Updated, false)])));
+        assert_eq!(EXIT_CHANGED, exit_code(&summary(vec![(Status::Pending, false)])));
+        assert_eq!(EXIT_PARTIAL_FAILURE, exit_code(&summary(vec![(Status::Created, false), (Status::Failed, true)])));
+        assert_eq!(EXIT_AUTH_ERROR, exit_code(&summary(vec![(Status::Failed, false), (Status::Failed, true)])));
+        assert_eq!(EXIT_PROVIDER_ERROR, exit_code(&summary(vec![(Status::Failed, false)])));
     }
 }
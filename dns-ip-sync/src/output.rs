@@ -0,0 +1,184 @@
+use std::str::FromStr;
+use dns_provider::{Change, Plan};
+use crate::sync::{Report, Status, Summary};
+
+/// How the result of a run is printed.
+///
+/// `json` prints one line per run with the schema of [`Summary`], so a
+/// daemon writes newline delimited json.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Output {
+    #[default]
+    Text,
+    Json,
+    Table,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Output::Text),
+            "json" => Ok(Output::Json),
+            "table" => Ok(Output::Table),
+            _ => Err(format!("Unknown output {}, expected text, json or table", value)),
+        }
+    }
+}
+
+impl Output {
+    pub fn print(&self, summary: &Summary) {
+        match self {
+            Output::Text => print_text(summary),
+            Output::Json => match serde_json::to_string(summary) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Could not print the result as json: {}", e),
+            },
+            Output::Table => print!("{}", table(summary)),
+        }
+    }
+}
+
+fn print_text(summary: &Summary) {
+    for report in &summary.targets {
+        match report.action {
+            Status::Failed => eprintln!(
+                "Could not sync {} {} in zone {}: {}",
+                report.record_type, report.name, report.zone, report.error.as_deref().unwrap_or_default()
+            ),
+            Status::Pending => println!(
+                "{} {} in zone {} -> {}: ttl lowered, changed after {}",
+                report.record_type, report.name, report.zone, value(&report.new_value), value(&report.pending_until)
+            ),
+            // unchanged records without plan were not looked up, as their address did not change
+            _ => match &report.plan {
+                Some(plan) if summary.dry_run => print_plan(plan),
+                Some(_) => println!(
+                    "{} {} in zone {} -> {}: {}",
+                    report.record_type, report.name, report.zone, value(&report.new_value), report.action
+                ),
+                None => {}
+            },
+        }
+    }
+}
+
+fn print_plan(plan: &Plan) {
+    for change in &plan.changes {
+        match change {
+            Change::Create(new) => println!(
+                "+ create {} {} in zone {}: {}", new.record_type, new.name, plan.zone.name, new.value
+            ),
+            Change::Update { old, new } => println!(
+                "~ update {} {} in zone {}: {} (ttl {}) -> {} (ttl {})",
+                new.record_type, new.name, plan.zone.name, old.value, ttl(old.ttl), new.value, ttl(new.ttl.or(old.ttl))
+            ),
+            Change::Delete(old) => println!(
+                "- delete {} {} in zone {}: {}", old.record_type, old.name, plan.zone.name, old.value
+            ),
+            Change::Unchanged(old) => println!(
+                "= keep {} {} in zone {}: {}", old.record_type, old.name, plan.zone.name, old.value
+            ),
+        }
+    }
+}
+
+fn ttl(ttl: Option<u32>) -> String {
+    ttl.map_or_else(|| String::from("default"), |ttl| ttl.to_string())
+}
+
+fn value(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("-")
+}
+
+fn table(summary: &Summary) -> String {
+    let header = ["ZONE", "NAME", "TYPE", "OLD", "NEW", "ACTION", "ERROR"].map(String::from);
+    let rows: Vec<[String; 7]> = summary.targets.iter().map(row).collect();
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(column.len());
+        }
+    }
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let columns: Vec<String> = row.iter()
+            .zip(widths.iter())
+            .map(|(column, width)| format!("{:width$}", column, width = width))
+            .collect();
+        table.push_str(columns.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn row(report: &Report) -> [String; 7] {
+    [
+        report.zone.clone(),
+        report.name.clone(),
+        report.record_type.clone(),
+        String::from(value(&report.old_value)),
+        String::from(value(&report.new_value)),
+        report.action.to_string(),
+        report.error.clone().unwrap_or_default(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Provider;
+
+    fn report(name: &str, old_value: Option<&str>, action: Status, error: Option<&str>) -> Report {
+        Report {
+            provider: Provider::Hetzner,
+            zone: String::from("example.com"),
+            name: String::from(name),
+            record_type: String::from("A"),
+            old_value: old_value.map(String::from),
+            new_value: Some(String::from("203.0.113.7")),
+            action,
+            pending_until: None,
+            error: error.map(String::from),
+            auth_failed: false,
+            plan: None,
+        }
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let summary = Summary {
+            changed: 1,
+            targets: vec![report("home", Some("192.0.2.1"), Status::Updated, None)],
+            ..Summary::default()
+        };
+        assert_eq!(
+            r#"{"dry_run":false,"changed":1,"unchanged":0,"pending":0,"failed":0,"targets":[{"provider":"hetzner","zone":"example.com","name":"home","type":"A","old_value":"192.0.2.1","new_value":"203.0.113.7","action":"updated","pending_until":null,"error":null}]}"#,
+            serde_json::to_string(&summary).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let summary = Summary {
+            targets: vec![
+                report("home", Some("192.0.2.1"), Status::Updated, None),
+                report("vpn", None, Status::Failed, Some("Not found")),
+            ],
+            ..Summary::default()
+        };
+        assert_eq!(
+            "ZONE         NAME  TYPE  OLD        NEW          ACTION   ERROR\n\
+             example.com  home  A     192.0.2.1  203.0.113.7  updated\n\
+             example.com  vpn   A     -          203.0.113.7  failed   Not found\n",
+            table(&summary)
+        );
+    }
+
+    #[test]
+    fn test_output_from_str() {
+        assert_eq!(Ok(Output::Json), "json".parse());
+        assert!("yaml".parse::<Output>().is_err());
+    }
+}
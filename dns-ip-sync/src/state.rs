@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+/// Value changes that wait for the old ttl to expire, kept in a toml file so
+/// the next run, e.g. of a cron job, can finish them.
+///
+/// # Example
+/// ```toml
+/// [[pending]]
+/// zone = "example.com"
+/// name = "home"
+/// type = "A"
+/// ready_at = 1621504800
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct State {
+    #[serde(default)]
+    pub pending: Vec<Pending>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pending {
+    pub zone: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    /// unix timestamp in seconds after which the value may be changed
+    pub ready_at: u64,
+}
+
+impl State {
+    /// Reads the state file, a missing file is an empty state.
+    pub fn load(path: &str) -> Result<State, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(toml::from_str(content.as_str())?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, zone: &str, name: &str, record_type: &str) -> Option<&Pending> {
+        self.pending.iter().find(|pending| pending.is(zone, name, record_type))
+    }
+
+    pub fn insert(&mut self, pending: Pending) {
+        self.remove(pending.zone.as_str(), pending.name.as_str(), pending.record_type.as_str());
+        self.pending.push(pending);
+    }
+
+    /// Removes the pending change of the record, returns true if there was one.
+    pub fn remove(&mut self, zone: &str, name: &str, record_type: &str) -> bool {
+        let count = self.pending.len();
+        self.pending.retain(|pending| !pending.is(zone, name, record_type));
+        count != self.pending.len()
+    }
+}
+
+impl Pending {
+    fn is(&self, zone: &str, name: &str, record_type: &str) -> bool {
+        self.zone == zone && self.name == name && self.record_type == record_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("dns-ip-sync-state-{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(State::default(), State::load(path).unwrap());
+
+        let mut state = State::default();
+        state.insert(Pending {
+            zone: String::from("example.com"),
+            name: String::from("home"),
+            record_type: String::from("A"),
+            ready_at: 1621504800,
+        });
+        state.insert(Pending {
+            zone: String::from("example.com"),
+            name: String::from("home"),
+            record_type: String::from("A"),
+            ready_at: 1621508400,
+        });
+        state.save(path).unwrap();
+        let loaded = State::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(state, loaded);
+        assert_eq!(1621508400, loaded.get("example.com", "home", "A").unwrap().ready_at);
+        assert!(loaded.get("example.com", "home", "AAAA").is_none());
+    }
+}
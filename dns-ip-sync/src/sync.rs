@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::error::Error;
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use cloudflare_dns_api::{CloudflareClient, CloudflareError, Credentials};
+use dns_provider::{plan_record, Action, Change, DnsProvider, Plan, Record, RecordCreate, RetryPolicy, Zone};
+use hetzner_dns_api::{HetznerClient, HetznerError};
+use serde::Serialize;
+use tracing::{debug, warn};
+use crate::config::{CloudflareSettings, Config, IpSource, Provider, Target};
+use crate::ip::{AddressFamily, IpResolver};
+use crate::state::{Pending, State};
+
+/// Syncs the targets of a config.
+///
+/// The provider clients and the last synced address of every target are kept
+/// between runs, so a long running process reuses its connections and only
+/// calls the provider when the address changed.
+///
+/// With `dry_run` the changes are only planned, nothing is written.
+pub struct Syncer {
+    config: Config,
+    dry_run: bool,
+    providers: HashMap<Provider, Box<dyn DnsProvider>>,
+    synced: HashMap<(usize, AddressFamily), IpAddr>,
+}
+
+/// The result of a run, in dry run mode `changed` counts the records with
+/// pending changes.
+///
+/// `pending` counts the records whose ttl was lowered and whose address is
+/// changed in a later run.
+#[derive(Serialize, Debug, Default)]
+pub struct Summary {
+    pub dry_run: bool,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub pending: usize,
+    pub failed: usize,
+    pub targets: Vec<Report>,
+}
+
+impl Summary {
+    fn add(&mut self, report: Report) {
+        match report.action {
+            Status::Created | Status::Updated => self.changed += 1,
+            Status::Unchanged => self.unchanged += 1,
+            Status::Pending => self.pending += 1,
+            Status::Failed => self.failed += 1,
+        }
+        self.targets.push(report);
+    }
+}
+
+/// What a run did to a single record of a target.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub provider: Provider,
+    pub zone: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub action: Status,
+    /// rfc 3339 time after which a pending address change is made
+    pub pending_until: Option<String>,
+    pub error: Option<String>,
+    /// the credentials are missing or were rejected
+    #[serde(skip)]
+    pub auth_failed: bool,
+    /// the changes that were, or in dry run mode would be, applied
+    #[serde(skip)]
+    pub plan: Option<Plan>,
+}
+
+impl Report {
+    fn new(target: &Target, family: AddressFamily, action: Status) -> Self {
+        Report {
+            provider: target.provider,
+            zone: target.zone.clone(),
+            name: target.name.clone(),
+            record_type: String::from(family.record_type()),
+            old_value: None,
+            new_value: None,
+            action,
+            pending_until: None,
+            error: None,
+            auth_failed: false,
+            plan: None,
+        }
+    }
+
+    fn failed(target: &Target, family: AddressFamily, error: &(dyn Error + 'static)) -> Self {
+        Report {
+            error: Some(error.to_string()),
+            auth_failed: is_auth_error(error),
+            ..Report::new(target, family, Status::Failed)
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Created,
+    Updated,
+    Unchanged,
+    /// the ttl was lowered, the address is changed in a later run
+    Pending,
+    Failed,
+}
+
+impl From<Action> for Status {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Created => Status::Created,
+            Action::Updated => Status::Updated,
+            Action::Unchanged => Status::Unchanged,
+        }
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Status::Created => write!(f, "created"),
+            Status::Updated => write!(f, "updated"),
+            Status::Unchanged => write!(f, "unchanged"),
+            Status::Pending => write!(f, "pending"),
+            Status::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// What a run does to a single record.
+enum Outcome {
+    /// nothing is written, in dry run mode or while a change is pending
+    Done(Action),
+    /// the address is changed once the old ttl expired, at this unix timestamp
+    Pending(u64),
+    /// the plan is written, with a timestamp it only lowers the ttl and the
+    /// address is changed after it
+    Apply(Option<u64>),
+}
+
+/// A record of a target whose plan is written together with the plans of
+/// the other targets of its provider, so they can share bulk requests.
+struct Write {
+    target: usize,
+    family: AddressFamily,
+    address: IpAddr,
+    ready_at: Option<u64>,
+    report: Report,
+}
+
+/// A zone with all of its records, or why they could not be fetched.
+type ZoneRecords = Result<(Zone, Vec<Record>), dns_provider::Error>;
+
+/// The report of a record, or the write it still waits for.
+enum Step {
+    Reported(Report),
+    Written(Write),
+}
+
+impl Syncer {
+    pub fn new(config: Config, dry_run: bool) -> Self {
+        Syncer {
+            config,
+            dry_run,
+            providers: HashMap::new(),
+            synced: HashMap::new(),
+        }
+    }
+
+    pub async fn run(&mut self) -> Summary {
+        let mut state = if self.config.lower_ttl {
+            State::load(self.config.state_file.as_str()).unwrap_or_else(|e| {
+                warn!("Could not read state {}: {}", self.config.state_file, e);
+                State::default()
+            })
+        } else {
+            State::default()
+        };
+        let loaded = state.clone();
+        let steps = self.plan(&state).await;
+        let summary = self.write(steps, &mut state).await;
+        if !self.dry_run && state != loaded {
+            if let Err(e) = state.save(self.config.state_file.as_str()) {
+                warn!("Could not write state {}: {}", self.config.state_file, e);
+            }
+        }
+        summary
+    }
+
+    /// Detects the addresses and plans the changes of every target.
+    ///
+    /// Targets are grouped by provider and zone, the zone and its records
+    /// are fetched once per group and shared by the plans of its targets.
+    async fn plan(&mut self, state: &State) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let lower_ttl = Some(self.config.default_ttl).filter(|_| self.config.lower_ttl);
+        let mut addresses: HashMap<(IpSource, AddressFamily), IpAddr> = HashMap::new();
+        let mut zones: HashMap<(Provider, String), ZoneRecords> = HashMap::new();
+        // a provider that could not be set up is retried in the next run only
+        let mut unavailable: HashMap<Provider, Box<dyn Error>> = HashMap::new();
+        for (index, target) in self.config.targets.iter().enumerate() {
+            if let Some(e) = unavailable.get(&target.provider) {
+                for family in target.record_type.families() {
+                    steps.push(Step::Reported(Report::failed(target, family, e.as_ref())));
+                }
+                continue;
+            }
+            let provider = match self.providers.entry(target.provider) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match dns_provider(target.provider, &self.config.cloudflare, self.config.retry.policy()).await {
+                    Ok(provider) => entry.insert(provider),
+                    Err(e) => {
+                        for family in target.record_type.families() {
+                            steps.push(Step::Reported(Report::failed(target, family, e.as_ref())));
+                        }
+                        unavailable.insert(target.provider, e);
+                        continue;
+                    }
+                },
+            };
+            for family in target.record_type.families() {
+                let key = (target.ip.clone(), family);
+                let ip_address = match addresses.get(&key) {
+                    Some(address) => *address,
+                    None => match IpResolver::new(&target.ip, &self.config.ip).resolve(family).await {
+                        Ok(address) => {
+                            debug!(source = ?target.ip, %address, "detected address");
+                            *addresses.entry(key).or_insert(address)
+                        }
+                        Err(e) => {
+                            steps.push(Step::Reported(Report::failed(target, family, e.as_ref())));
+                            continue;
+                        }
+                    },
+                };
+                if self.synced.get(&(index, family)) == Some(&ip_address) {
+                    debug!(zone = %target.zone, name = %target.name, address = %ip_address, "address already synced");
+                    steps.push(Step::Reported(Report {
+                        old_value: Some(ip_address.to_string()),
+                        new_value: Some(ip_address.to_string()),
+                        ..Report::new(target, family, Status::Unchanged)
+                    }));
+                    continue;
+                }
+                let record = RecordCreate {
+                    name: target.name.clone(),
+                    record_type: String::from(family.record_type()),
+                    value: ip_address.to_string(),
+                    ttl: target.ttl,
+                    proxied: target.proxied,
+                };
+                let zone = match zones.entry((target.provider, target.zone.to_lowercase())) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(zone_records(provider.as_ref(), target.zone.as_str()).await),
+                };
+                let planned = match zone {
+                    Ok((zone, records)) => plan_record(zone, records, &record, target.duplicates),
+                    Err(e) => {
+                        steps.push(Step::Reported(Report::failed(target, family, e.as_ref())));
+                        continue;
+                    }
+                };
+                let plan = match planned {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        steps.push(Step::Reported(Report::failed(target, family, e.as_ref())));
+                        continue;
+                    }
+                };
+                // proxied records always have the automatic ttl of cloudflare
+                let plan = match target.proxied {
+                    Some(true) => plan,
+                    _ => plan.with_default_ttl(self.config.default_ttl),
+                };
+                debug!(zone = %target.zone, name = %target.name, changes = ?plan.changes, "planned changes");
+                let old_value = old_value(&plan);
+                let (outcome, plan) = match sync_plan(target.zone.as_str(), &record, plan, lower_ttl, self.dry_run, state) {
+                    Ok(planned) => planned,
+                    Err(e) => {
+                        steps.push(Step::Reported(Report::failed(target, family, e.as_ref())));
+                        continue;
+                    }
+                };
+                let report = Report {
+                    old_value,
+                    new_value: Some(record.value.clone()),
+                    plan: Some(plan),
+                    ..Report::new(target, family, Status::Unchanged)
+                };
+                steps.push(match outcome {
+                    Outcome::Done(action) => Step::Reported(Report { action: Status::from(action), ..report }),
+                    Outcome::Pending(ready_at) => Step::Reported(Report {
+                        action: Status::Pending,
+                        pending_until: Some(format_time(ready_at)),
+                        ..report
+                    }),
+                    Outcome::Apply(ready_at) => Step::Written(Write {
+                        target: index,
+                        family,
+                        address: ip_address,
+                        ready_at,
+                        report,
+                    }),
+                });
+            }
+        }
+        steps
+    }
+
+    /// Writes the planned changes with one call per provider, and reports
+    /// every record in the order of the targets.
+    async fn write(&mut self, steps: Vec<Step>, state: &mut State) -> Summary {
+        let mut summary = Summary { dry_run: self.dry_run, ..Summary::default() };
+        let mut results: HashMap<usize, Result<Action, dns_provider::Error>> = HashMap::new();
+        for (provider_type, provider) in &self.providers {
+            let writes: Vec<(usize, &Write)> = steps.iter()
+                .enumerate()
+                .filter_map(|(index, step)| match step {
+                    Step::Written(write) if write.report.provider == *provider_type => Some((index, write)),
+                    _ => None,
+                })
+                .collect();
+            if writes.is_empty() {
+                continue;
+            }
+            let plans: Vec<Plan> = writes.iter()
+                .filter_map(|(_, write)| write.report.plan.clone())
+                .collect();
+            for ((index, _), result) in writes.iter().zip(provider.apply_all(&plans).await) {
+                results.insert(*index, result);
+            }
+        }
+        for (index, step) in steps.into_iter().enumerate() {
+            let write = match step {
+                Step::Reported(report) => {
+                    summary.add(report);
+                    continue;
+                }
+                Step::Written(write) => write,
+            };
+            let target = &self.config.targets[write.target];
+            let report = write.report;
+            let pending = (report.zone.as_str(), report.name.as_str(), report.record_type.as_str());
+            summary.add(match (results.remove(&index), write.ready_at) {
+                (Some(Ok(_)), Some(ready_at)) => {
+                    state.insert(Pending {
+                        zone: String::from(pending.0),
+                        name: String::from(pending.1),
+                        record_type: String::from(pending.2),
+                        ready_at,
+                    });
+                    Report {
+                        action: Status::Pending,
+                        pending_until: Some(format_time(ready_at)),
+                        ..report
+                    }
+                }
+                (Some(Ok(action)), None) => {
+                    state.remove(pending.0, pending.1, pending.2);
+                    self.synced.insert((write.target, write.family), write.address);
+                    Report { action: Status::from(action), ..report }
+                }
+                (Some(Err(e)), _) => Report::failed(target, write.family, e.as_ref()),
+                (None, _) => {
+                    let e: Box<dyn Error> = Box::from("The provider returned no result for the record");
+                    Report::failed(target, write.family, e.as_ref())
+                }
+            });
+        }
+        summary
+    }
+}
+
+/// Fetches the zone with this name and all of its records.
+async fn zone_records(provider: &dyn DnsProvider, zone_name: &str) -> ZoneRecords {
+    let zone = provider.get_zone_by_name(zone_name).await?;
+    let records = provider.get_records(&zone).await?;
+    Ok((zone, records))
+}
+
+/// Returns true if the credentials of a provider are missing or were rejected.
+fn is_auth_error(error: &(dyn Error + 'static)) -> bool {
+    matches!(error.downcast_ref::<HetznerError>(), Some(HetznerError::Auth(_)))
+        || matches!(error.downcast_ref::<CloudflareError>(), Some(CloudflareError::Auth(_)))
+}
+
+/// The current value of the record the plan keeps, `None` if it creates one.
+fn old_value(plan: &Plan) -> Option<String> {
+    plan.changes.iter().find_map(|change| match change {
+        Change::Update { old, .. } | Change::Unchanged(old) => Some(old.value.clone()),
+        _ => None,
+    })
+}
+
+fn format_time(timestamp: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(timestamp)).to_string()
+}
+
+/// Decides what happens with `plan`: with `lower_ttl` a record whose address
+/// changes only gets a lower ttl first, and the change stays pending in
+/// `state` until the old ttl expired.
+///
+/// Returns the plan that is, or in dry run mode would be, applied.
+fn sync_plan(
+    zone: &str,
+    record: &RecordCreate,
+    plan: Plan,
+    lower_ttl: Option<u32>,
+    dry_run: bool,
+    state: &State,
+) -> Result<(Outcome, Plan), dns_provider::Error> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let name = record.name.as_str();
+    let record_type = record.record_type.as_str();
+    if plan.value_update().is_some() {
+        match state.get(zone, name, record_type) {
+            Some(pending) if now < pending.ready_at => {
+                let ready_at = pending.ready_at;
+                return Ok((Outcome::Pending(ready_at), Plan { changes: vec![], ..plan }));
+            }
+            Some(_) => {}
+            None => if let Some((lowering, wait)) = lower_ttl.and_then(|ttl| plan.lower_ttl(ttl)) {
+                if dry_run {
+                    return Ok((Outcome::Done(lowering.action()), lowering));
+                }
+                return Ok((Outcome::Apply(Some(now + wait.as_secs())), lowering));
+            },
+        }
+    }
+    if dry_run {
+        return Ok((Outcome::Done(plan.action()), plan));
+    }
+    Ok((Outcome::Apply(None), plan))
+}
+
+async fn dns_provider(provider: Provider, cloudflare: &CloudflareSettings, retry: RetryPolicy) -> Result<Box<dyn DnsProvider>, Box<dyn Error>> {
+    Ok(match provider {
+        Provider::Cloudflare => Box::new(cloudflare_client(cloudflare, retry).await?),
+        Provider::Hetzner => Box::new(HetznerClient::builder_from_env()?.retry(retry).build()?),
+    })
+}
+
+/// Creates the cloudflare client and verifies its api token, so a revoked or
+/// expired token fails before any record is touched.
+async fn cloudflare_client(settings: &CloudflareSettings, retry: RetryPolicy) -> Result<CloudflareClient, Box<dyn Error>> {
+    let credentials = match settings {
+        CloudflareSettings { api_token: Some(token), .. } => Credentials::Token(token.clone()),
+        CloudflareSettings { key: Some(key), email: Some(email), .. } => Credentials::Key {
+            key: key.clone(),
+            email: email.clone(),
+        },
+        _ => Credentials::from_env()?,
+    };
+    let client = CloudflareClient::builder(credentials).retry(retry).build()?;
+    if let Credentials::Token(_) = client.credentials() {
+        let status = client.verify_token().await?.result;
+        match status {
+            Some(status) if status.is_active() => {}
+            Some(status) => return Err(Box::new(CloudflareError::Auth(format!("Cloudflare api token is {}", status.status)))),
+            None => return Err(Box::new(CloudflareError::Auth(String::from("Cloudflare api token could not be verified")))),
+        }
+    }
+    Ok(client)
+}
@@ -0,0 +1,589 @@
+mod plan;
+mod retry;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+pub use plan::{Change, Plan};
+pub use retry::{jitter, ProviderHeaders, RateLimitHeaders, RetryPolicy};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// How `plan_record` handles several records with the same name and type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    /// updates the first record, preferring one that already matches, and
+    /// deletes the others afterwards, so the name never resolves to nothing
+    #[default]
+    KeepFirst,
+    /// fails with [`DuplicateRecordsError`] without touching any record
+    Refuse,
+    /// treats the records as a round robin set shared with other hosts: the
+    /// value is added if no record has it yet, the other records are kept
+    RoundRobin,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "keep-first" => Ok(DuplicatePolicy::KeepFirst),
+            "refuse" => Ok(DuplicatePolicy::Refuse),
+            "round-robin" => Ok(DuplicatePolicy::RoundRobin),
+            _ => Err(format!("Unknown duplicate policy {}, expected keep-first, refuse or round-robin", value)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DuplicateRecordsError {
+    pub name: String,
+    pub record_type: String,
+    pub count: usize,
+}
+
+impl std::fmt::Display for DuplicateRecordsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "There are {} {} records for {}, refusing to change them.", self.count, self.record_type, self.name)
+    }
+}
+
+impl std::error::Error for DuplicateRecordsError {}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub id: String,
+    pub name: String,
+}
+
+/// A record as returned by a provider.
+///
+/// `name` is always relative to the zone, `@` is used for the apex.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    pub id: String,
+    pub zone_id: String,
+    pub name: String,
+    pub record_type: String,
+    pub value: String,
+    pub ttl: Option<u32>,
+    /// whether the provider proxies the traffic, `None` if it can not proxy
+    pub proxied: Option<bool>,
+}
+
+/// A record to write, `None` fields keep what the provider has or defaults to.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RecordCreate {
+    pub name: String,
+    pub record_type: String,
+    pub value: String,
+    pub ttl: Option<u32>,
+    pub proxied: Option<bool>,
+}
+
+/// What `create_update_record` did to bring the record up to date.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Created,
+    Updated,
+    /// the record already matched, nothing was written
+    Unchanged,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Action::Created => write!(f, "created"),
+            Action::Updated => write!(f, "updated"),
+            Action::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+/// Returns true if `record` already has the value of `desired`.
+///
+/// Addresses are compared parsed, so differently written IPv6 addresses match.
+pub fn value_matches(record: &Record, desired: &RecordCreate) -> bool {
+    match (record.value.parse::<IpAddr>(), desired.value.parse::<IpAddr>()) {
+        (Ok(current), Ok(desired)) => current == desired,
+        _ => record.value == desired.value,
+    }
+}
+
+/// Returns true if `record` already has the value, ttl and proxy setting of `desired`.
+///
+/// A missing ttl or proxy setting in `desired` keeps whatever the record has,
+/// the proxy setting is ignored for providers that can not proxy.
+pub fn record_matches(record: &Record, desired: &RecordCreate) -> bool {
+    let same_ttl = desired.ttl.is_none() || record.ttl == desired.ttl;
+    let same_proxied = desired.proxied.is_none() || record.proxied.is_none() || record.proxied == desired.proxied;
+    value_matches(record, desired) && same_ttl && same_proxied
+}
+
+/// Converts `name` into a lowercase name relative to `zone`, e.g.
+/// `www.example.com` in the zone `example.com` becomes `www` and
+/// `example.com` becomes `@`.
+///
+/// Names are only equal if their relative names are, so `www` never matches `www2`.
+pub fn relative_name(name: &str, zone: &str) -> String {
+    let name = name.trim_end_matches('.').to_lowercase();
+    let zone = zone.trim_end_matches('.').to_lowercase();
+    let name = name.as_str();
+    let zone = zone.as_str();
+    if name == zone {
+        return String::from("@");
+    }
+    match name.strip_suffix(zone).and_then(|prefix| prefix.strip_suffix('.')) {
+        Some(prefix) => String::from(prefix),
+        None => String::from(name),
+    }
+}
+
+/// Plans the changes needed so that there is a record with the name, type
+/// and value of `record` in `zone`, whose current records are `records`.
+/// Nothing is fetched or written, so the records of a zone can be fetched
+/// once and shared by the plans of all its names.
+///
+/// An existing record is updated unless it already matches, see
+/// [`record_matches`]. Several existing records are handled by `policy`.
+pub fn plan_record(zone: &Zone, records: &[Record], record: &RecordCreate, policy: DuplicatePolicy) -> Result<Plan, Error> {
+    let zone = zone.clone();
+    let record = RecordCreate {
+        name: relative_name(record.name.as_str(), zone.name.as_str()),
+        ..record.clone()
+    };
+    let mut existing = records.iter()
+        .filter(|existing| {
+            relative_name(existing.name.as_str(), zone.name.as_str()) == record.name
+                && existing.record_type.eq_ignore_ascii_case(record.record_type.as_str())
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if existing.is_empty() {
+        return Ok(Plan { zone, changes: vec![Change::Create(record)] });
+    }
+    let changes = match policy {
+        DuplicatePolicy::Refuse if existing.len() > 1 => {
+            return Err(Box::new(DuplicateRecordsError {
+                name: record.name,
+                record_type: record.record_type,
+                count: existing.len(),
+            }));
+        }
+        DuplicatePolicy::RoundRobin => {
+            let member = existing.iter().position(|existing| value_matches(existing, &record));
+            let mut changes = Vec::new();
+            for (index, old) in existing.into_iter().enumerate() {
+                if Some(index) == member && !record_matches(&old, &record) {
+                    changes.push(Change::update(old, &record));
+                } else {
+                    changes.push(Change::Unchanged(old));
+                }
+            }
+            if member.is_none() {
+                changes.push(Change::Create(record));
+            }
+            changes
+        }
+        _ => {
+            // the update comes before the deletes, so the name always resolves
+            let keep = existing.iter().position(|existing| record_matches(existing, &record)).unwrap_or(0);
+            let old = existing.remove(keep);
+            let mut changes = if record_matches(&old, &record) {
+                vec![Change::Unchanged(old)]
+            } else {
+                vec![Change::update(old, &record)]
+            };
+            changes.extend(existing.into_iter().map(Change::Delete));
+            changes
+        }
+    };
+    Ok(Plan { zone, changes })
+}
+
+/// Operations every dns provider has to implement.
+///
+/// The sync logic (`create_update_record`, ...) is provided on top of them,
+/// so it is shared by all providers.
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    async fn get_zone_by_name(&self, name: &str) -> Result<Zone, Error>;
+
+    async fn get_records(&self, zone: &Zone) -> Result<Vec<Record>, Error>;
+
+    async fn create_record(&self, zone: &Zone, record: &RecordCreate) -> Result<(), Error>;
+
+    async fn update_record(&self, zone: &Zone, record_id: &str, record: &RecordCreate) -> Result<(), Error>;
+
+    async fn delete_record(&self, zone: &Zone, record_id: &str) -> Result<(), Error>;
+
+    async fn get_all_records_by_name(&self, zone_name: &str) -> Result<Vec<Record>, Error> {
+        let zone = self.get_zone_by_name(zone_name).await?;
+        self.get_records(&zone).await
+    }
+
+    /// Deletes all records of the name with the type, e.g. `A`, other types
+    /// of the name are kept.
+    async fn delete_records_by_name(&self, zone_name: &str, record_name: &str, record_type: &str) -> Result<(), Error> {
+        let zone = self.get_zone_by_name(zone_name).await?;
+        let record_name = relative_name(record_name, zone.name.as_str());
+        for record in self.get_records(&zone).await? {
+            if relative_name(record.name.as_str(), zone.name.as_str()) == record_name
+                && record.record_type.eq_ignore_ascii_case(record_type) {
+                self.delete_record(&zone, record.id.as_str()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the changes of `plan` in order.
+    async fn apply(&self, plan: &Plan) -> Result<Action, Error> {
+        for change in &plan.changes {
+            match change {
+                Change::Create(record) => self.create_record(&plan.zone, record).await?,
+                Change::Update { old, new } => self.update_record(&plan.zone, old.id.as_str(), new).await?,
+                Change::Delete(record) => self.delete_record(&plan.zone, record.id.as_str()).await?,
+                Change::Unchanged(_) => {}
+            }
+        }
+        Ok(plan.action())
+    }
+
+    /// Applies several plans and returns the result of every plan in the
+    /// same order. Providers with bulk endpoints write the changes of a zone
+    /// with fewer requests.
+    async fn apply_all(&self, plans: &[Plan]) -> Vec<Result<Action, Error>> {
+        let mut results = Vec::with_capacity(plans.len());
+        for plan in plans {
+            results.push(self.apply(plan).await);
+        }
+        results
+    }
+
+    /// Plans and applies the changes for `record`, see [`plan_record`].
+    async fn create_update_record(&self, zone_name: &str, record: &RecordCreate, policy: DuplicatePolicy) -> Result<Action, Error> {
+        let zone = self.get_zone_by_name(zone_name).await?;
+        let records = self.get_records(&zone).await?;
+        let plan = plan_record(&zone, &records, record, policy)?;
+        self.apply(&plan).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::runtime::Runtime;
+
+    struct MemoryProvider {
+        records: Mutex<Vec<Record>>,
+        next_id: Mutex<u32>,
+        writes: Mutex<u32>,
+    }
+
+    impl MemoryProvider {
+        fn new(records: Vec<(&str, &str, &str)>) -> Self {
+            let records = records.into_iter().enumerate().map(|(id, (name, record_type, value))| Record {
+                id: id.to_string(),
+                zone_id: String::from("1"),
+                name: String::from(name),
+                record_type: String::from(record_type),
+                value: String::from(value),
+                ttl: None,
+                proxied: Some(false),
+            }).collect::<Vec<_>>();
+            let next_id = Mutex::new(records.len() as u32);
+            MemoryProvider { records: Mutex::new(records), next_id, writes: Mutex::new(0) }
+        }
+
+        fn values(&self, name: &str, record_type: &str) -> Vec<String> {
+            self.records.lock().unwrap().iter()
+                .filter(|record| record.name == name && record.record_type == record_type)
+                .map(|record| record.value.clone())
+                .collect()
+        }
+
+        async fn plan(&self, record: &RecordCreate, policy: DuplicatePolicy) -> Result<Plan, Error> {
+            let zone = self.get_zone_by_name("example.com").await?;
+            plan_record(&zone, &self.get_records(&zone).await?, record, policy)
+        }
+    }
+
+    #[async_trait]
+    impl DnsProvider for MemoryProvider {
+        async fn get_zone_by_name(&self, name: &str) -> Result<Zone, Error> {
+            match name {
+                "example.com" => Ok(Zone { id: String::from("1"), name: String::from(name) }),
+                _ => Err(Error::from(format!("No Zone found for name {}.", name))),
+            }
+        }
+
+        async fn get_records(&self, _zone: &Zone) -> Result<Vec<Record>, Error> {
+            Ok(self.records.lock().unwrap().clone())
+        }
+
+        async fn create_record(&self, zone: &Zone, record: &RecordCreate) -> Result<(), Error> {
+            *self.writes.lock().unwrap() += 1;
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            self.records.lock().unwrap().push(Record {
+                id: next_id.to_string(),
+                zone_id: zone.id.clone(),
+                name: record.name.clone(),
+                record_type: record.record_type.clone(),
+                value: record.value.clone(),
+                ttl: record.ttl,
+                proxied: Some(record.proxied.unwrap_or_default()),
+            });
+            Ok(())
+        }
+
+        async fn update_record(&self, _zone: &Zone, record_id: &str, record: &RecordCreate) -> Result<(), Error> {
+            *self.writes.lock().unwrap() += 1;
+            let mut records = self.records.lock().unwrap();
+            let existing = records.iter_mut().find(|existing| existing.id == record_id).unwrap();
+            existing.value = record.value.clone();
+            existing.ttl = record.ttl;
+            existing.proxied = record.proxied.or(existing.proxied);
+            Ok(())
+        }
+
+        async fn delete_record(&self, _zone: &Zone, record_id: &str) -> Result<(), Error> {
+            *self.writes.lock().unwrap() += 1;
+            self.records.lock().unwrap().retain(|record| record.id != record_id);
+            Ok(())
+        }
+    }
+
+    fn record(name: &str, record_type: &str, value: &str) -> RecordCreate {
+        RecordCreate {
+            name: String::from(name),
+            record_type: String::from(record_type),
+            value: String::from(value),
+            ttl: None,
+            proxied: None,
+        }
+    }
+
+    #[test]
+    fn test_relative_name() {
+        assert_eq!("@", relative_name("example.com", "example.com"));
+        assert_eq!("@", relative_name("example.com.", "example.com"));
+        assert_eq!("www", relative_name("www.example.com", "example.com"));
+        assert_eq!("www", relative_name("www", "example.com"));
+        assert_eq!("wwwexample.com", relative_name("wwwexample.com", "example.com"));
+    }
+
+    #[test]
+    fn test_relative_name_ignores_case() {
+        assert_eq!("www", relative_name("WWW.Example.com", "example.COM"));
+        assert_eq!("@", relative_name("Example.com", "example.com"));
+    }
+
+    #[test]
+    fn test_create_update_record() {
+        let provider = MemoryProvider::new(vec![
+            ("home", "A", "192.0.2.1"),
+            ("home", "AAAA", "2001:db8::1"),
+            ("home2", "A", "192.0.2.2"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let action = provider.create_update_record("example.com", &record("home.example.com", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(Action::Updated, action);
+            assert_eq!(vec!["203.0.113.7"], provider.values("home", "A"));
+            assert_eq!(vec!["2001:db8::1"], provider.values("home", "AAAA"));
+            assert_eq!(vec!["192.0.2.2"], provider.values("home2", "A"));
+
+            let action = provider.create_update_record("example.com", &record("vpn", "A", "203.0.113.8"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(Action::Created, action);
+            assert_eq!(vec!["203.0.113.8"], provider.values("vpn", "A"));
+
+            let result = provider.create_update_record("example.org", &record("vpn", "A", "203.0.113.8"), DuplicatePolicy::KeepFirst).await;
+            assert!(result.is_err());
+        });
+    }
+
+    #[test]
+    fn test_delete_records_by_name() {
+        let provider = MemoryProvider::new(vec![
+            ("home", "A", "192.0.2.1"),
+            ("home", "A", "192.0.2.2"),
+            ("home", "AAAA", "2001:db8::1"),
+            ("home2", "A", "192.0.2.3"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            provider.delete_records_by_name("example.com", "home.example.com", "a").await.unwrap();
+            assert!(provider.values("home", "A").is_empty());
+            assert_eq!(vec!["2001:db8::1"], provider.values("home", "AAAA"));
+            assert_eq!(vec!["192.0.2.3"], provider.values("home2", "A"));
+        });
+    }
+
+    #[test]
+    fn test_create_update_record_unchanged() {
+        let provider = MemoryProvider::new(vec![
+            ("home", "A", "203.0.113.7"),
+            ("home", "AAAA", "2001:db8:0:0::1"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let action = provider.create_update_record("example.com", &record("home", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(Action::Unchanged, action);
+            let action = provider.create_update_record("example.com", &record("home", "AAAA", "2001:db8::1"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(Action::Unchanged, action);
+            assert_eq!(0, *provider.writes.lock().unwrap());
+
+            let with_ttl = RecordCreate { ttl: Some(60), ..record("home", "A", "203.0.113.7") };
+            let action = provider.create_update_record("example.com", &with_ttl, DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(Action::Updated, action);
+            assert_eq!(1, *provider.writes.lock().unwrap());
+
+            let proxied = RecordCreate { proxied: Some(true), ..with_ttl.clone() };
+            let action = provider.create_update_record("example.com", &proxied, DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(Action::Updated, action);
+            let action = provider.create_update_record("example.com", &proxied, DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(Action::Unchanged, action);
+        });
+    }
+
+    #[test]
+    fn test_plan_record_does_not_write() {
+        let provider = MemoryProvider::new(vec![
+            ("home", "A", "192.0.2.1"),
+            ("home", "A", "192.0.2.2"),
+            ("vpn", "A", "192.0.2.3"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let plan = provider.plan(&record("home", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(2, plan.changes.len());
+            assert!(matches!(&plan.changes[0], Change::Update { old, .. } if old.value == "192.0.2.1"));
+            assert!(matches!(&plan.changes[1], Change::Delete(old) if old.value == "192.0.2.2"));
+            assert_eq!(Action::Updated, plan.action());
+
+            let plan = provider.plan(&record("vpn", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert!(plan.has_changes());
+            assert_eq!(Action::Updated, plan.action());
+
+            let plan = provider.plan(&record("vpn", "A", "192.0.2.3"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert!(!plan.has_changes());
+            assert_eq!(0, *provider.writes.lock().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_apply_all() {
+        let provider = MemoryProvider::new(vec![("home", "A", "192.0.2.1")]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let update = provider.plan(&record("home", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            let create = provider.plan(&record("vpn", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            let results = provider.apply_all(&[update, create]).await;
+            let actions: Vec<Action> = results.into_iter().map(Result::unwrap).collect();
+            assert_eq!(vec![Action::Updated, Action::Created], actions);
+            assert_eq!(vec!["203.0.113.7"], provider.values("vpn", "A"));
+        });
+    }
+
+    #[test]
+    fn test_create_update_record_replaces_duplicates() {
+        let provider = MemoryProvider::new(vec![
+            ("home", "A", "192.0.2.1"),
+            ("home", "A", "192.0.2.2"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            provider.create_update_record("example.com", &record("home", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert_eq!(vec!["203.0.113.7"], provider.values("home", "A"));
+        });
+    }
+
+    #[test]
+    fn test_keep_first_prefers_matching_record() {
+        let provider = MemoryProvider::new(vec![
+            ("HOME", "A", "192.0.2.1"),
+            ("home", "A", "203.0.113.7"),
+            ("home2", "A", "192.0.2.2"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let plan = provider.plan(&record("home", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert!(matches!(&plan.changes[0], Change::Unchanged(old) if old.value == "203.0.113.7"));
+            assert!(matches!(&plan.changes[1], Change::Delete(old) if old.value == "192.0.2.1"));
+            assert_eq!(2, plan.changes.len());
+        });
+    }
+
+    #[test]
+    fn test_refuse_duplicates() {
+        let provider = MemoryProvider::new(vec![
+            ("home", "A", "192.0.2.1"),
+            ("home", "A", "192.0.2.2"),
+            ("vpn", "A", "192.0.2.3"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = provider.create_update_record("example.com", &record("home", "A", "203.0.113.7"), DuplicatePolicy::Refuse).await.unwrap_err();
+            let error = error.downcast_ref::<DuplicateRecordsError>().unwrap();
+            assert_eq!(2, error.count);
+            assert_eq!(0, *provider.writes.lock().unwrap());
+
+            let action = provider.create_update_record("example.com", &record("vpn", "A", "203.0.113.7"), DuplicatePolicy::Refuse).await.unwrap();
+            assert_eq!(Action::Updated, action);
+        });
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let provider = MemoryProvider::new(vec![
+            ("home", "A", "192.0.2.1"),
+            ("home", "A", "192.0.2.2"),
+        ]);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let action = provider.create_update_record("example.com", &record("home", "A", "192.0.2.2"), DuplicatePolicy::RoundRobin).await.unwrap();
+            assert_eq!(Action::Unchanged, action);
+
+            let action = provider.create_update_record("example.com", &record("home", "A", "203.0.113.7"), DuplicatePolicy::RoundRobin).await.unwrap();
+            assert_eq!(Action::Created, action);
+            assert_eq!(vec!["192.0.2.1", "192.0.2.2", "203.0.113.7"], provider.values("home", "A"));
+        });
+    }
+
+    #[test]
+    fn test_duplicate_policy_from_str() {
+        assert_eq!(Ok(DuplicatePolicy::KeepFirst), "keep-first".parse());
+        assert_eq!(Ok(DuplicatePolicy::Refuse), "refuse".parse());
+        assert_eq!(Ok(DuplicatePolicy::RoundRobin), "round-robin".parse());
+        assert!("all".parse::<DuplicatePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_lower_ttl() {
+        let provider = MemoryProvider::new(vec![("home", "A", "192.0.2.1"), ("vpn", "A", "192.0.2.2")]);
+        provider.records.lock().unwrap()[0].ttl = Some(3600);
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let plan = provider.plan(&record("home", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert!(matches!(&plan.changes[0], Change::Update { new, .. } if new.ttl == Some(3600)));
+            let (lowering, wait) = plan.lower_ttl(60).unwrap();
+            assert_eq!(std::time::Duration::from_secs(3600), wait);
+            assert!(matches!(&lowering.changes[0], Change::Update { new, .. } if new.value == "192.0.2.1" && new.ttl == Some(60)));
+            provider.apply(&lowering).await.unwrap();
+
+            let plan = provider.plan(&record("home", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert!(plan.value_update().is_some());
+            assert!(plan.lower_ttl(60).is_none());
+
+            let plan = provider.plan(&record("www", "A", "203.0.113.7"), DuplicatePolicy::KeepFirst).await.unwrap();
+            assert!(plan.lower_ttl(60).is_none());
+            assert_eq!(Change::Create(RecordCreate { ttl: Some(60), ..record("www", "A", "203.0.113.7") }), plan.with_default_ttl(60).changes[0]);
+        });
+    }
+}
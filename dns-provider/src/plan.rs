@@ -0,0 +1,86 @@
+use serde::Serialize;
+use std::time::Duration;
+use crate::{value_matches, Action, Record, RecordCreate, Zone};
+
+/// A single change needed to bring a record up to date.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum Change {
+    Create(RecordCreate),
+    Update { old: Record, new: RecordCreate },
+    Delete(Record),
+    /// the record already matches, nothing has to be written
+    Unchanged(Record),
+}
+
+impl Change {
+    /// Updates `old` to `new`, the ttl and proxy setting `new` leaves open are
+    /// taken from `old`, so providers that reset missing fields keep them.
+    pub fn update(old: Record, new: &RecordCreate) -> Change {
+        let new = RecordCreate {
+            ttl: new.ttl.or(old.ttl),
+            proxied: new.proxied.or(old.proxied),
+            ..new.clone()
+        };
+        Change::Update { old, new }
+    }
+}
+
+/// The changes `create_update_record` makes, in the order they are applied.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub zone: Zone,
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    pub fn has_changes(&self) -> bool {
+        self.changes.iter().any(|change| !matches!(change, Change::Unchanged(_)))
+    }
+
+    /// Sets `ttl` on the records this plan creates without a ttl.
+    pub fn with_default_ttl(mut self, ttl: u32) -> Plan {
+        for change in self.changes.iter_mut() {
+            if let Change::Create(record) = change {
+                record.ttl = record.ttl.or(Some(ttl));
+            }
+        }
+        self
+    }
+
+    /// The first update of this plan that changes the value of a record.
+    pub fn value_update(&self) -> Option<(&Record, &RecordCreate)> {
+        self.changes.iter().find_map(|change| match change {
+            Change::Update { old, new } if !value_matches(old, new) => Some((old, new)),
+            _ => None,
+        })
+    }
+
+    /// Returns the first phase of a value update of a record with a ttl above
+    /// `ttl`: the plan that only lowers its ttl, and how long resolvers may
+    /// still cache the record with the old ttl.
+    pub fn lower_ttl(&self, ttl: u32) -> Option<(Plan, Duration)> {
+        let (old, new) = self.value_update()?;
+        let old_ttl = old.ttl.filter(|old_ttl| *old_ttl > ttl)?;
+        let lowered = RecordCreate {
+            value: old.value.clone(),
+            ttl: Some(ttl),
+            ..new.clone()
+        };
+        let plan = Plan {
+            zone: self.zone.clone(),
+            changes: vec![Change::Update { old: old.clone(), new: lowered }],
+        };
+        Some((plan, Duration::from_secs(u64::from(old_ttl))))
+    }
+
+    /// The action that applying this plan results in.
+    pub fn action(&self) -> Action {
+        if self.changes.iter().any(|change| matches!(change, Change::Create(_))) {
+            Action::Created
+        } else if self.has_changes() {
+            Action::Updated
+        } else {
+            Action::Unchanged
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use reqwest::header::HeaderMap;
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, trace, warn};
+
+/// When and how long a failed request is retried: exponential backoff with
+/// jitter, unless the server tells how long to wait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// attempts including the first one, 1 never retries
+    pub max_attempts: u32,
+    /// no retry is started if it would begin after this time since the first attempt
+    pub deadline: Duration,
+    /// delay before the first retry, doubled for every further retry
+    pub base_delay: Duration,
+    /// the backoff never waits longer than this
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            deadline: Duration::from_secs(60),
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request only once.
+    pub fn never() -> Self {
+        RetryPolicy { max_attempts: 1, ..RetryPolicy::default() }
+    }
+
+    /// Returns how long to wait before the next attempt after `attempt`
+    /// failed, or `None` if the attempts or the deadline are used up.
+    ///
+    /// `wait` is the delay the server asked for, e.g. with `Retry-After`,
+    /// and replaces the backoff.
+    pub fn delay(&self, attempt: u32, elapsed: Duration, wait: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let delay = wait.unwrap_or_else(|| self.backoff(attempt));
+        if elapsed + delay > self.deadline {
+            return None;
+        }
+        Some(delay)
+    }
+
+    /// Sends the request and retries it if it was rate limited, or if an
+    /// idempotent request failed with a transient status or could not be sent.
+    ///
+    /// Every attempt is logged with its method, url, status and latency at
+    /// debug level, and its headers with redacted credentials at trace level.
+    pub async fn execute(&self, provider: &ProviderHeaders, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let (client, request) = request.build_split();
+        let mut request = request?;
+        let method = request.method().clone();
+        let url = request.url().clone();
+        let start = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let next = request.try_clone();
+            trace!(%method, %url, attempt, headers = ?provider.redacted_headers(request.headers()), "sending request");
+            let sent = Instant::now();
+            let result = client.execute(request).await;
+            let latency_ms = sent.elapsed().as_millis() as u64;
+            let wait = match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    debug!(%method, %url, attempt, status = status.as_u16(), latency_ms, "request finished");
+                    if status == StatusCode::TOO_MANY_REQUESTS || (is_transient(status) && is_idempotent(&method)) {
+                        Some(provider.retry_after(response.headers()).map(Duration::from_secs))
+                    } else {
+                        None
+                    }
+                }
+                Err(e) => {
+                    debug!(%method, %url, attempt, error = %e, latency_ms, "request failed");
+                    if is_idempotent(&method) && (e.is_connect() || e.is_timeout()) {
+                        Some(None)
+                    } else {
+                        None
+                    }
+                }
+            };
+            let delay = wait.and_then(|wait| self.delay(attempt, start.elapsed(), wait));
+            match (next, delay) {
+                (Some(next), Some(delay)) => {
+                    warn!(%method, %url, attempt, delay_ms = delay.as_millis() as u64, "retrying request");
+                    sleep(delay).await;
+                    request = next;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    /// Exponential backoff with jitter, between half and the full delay.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.checked_mul(factor).unwrap_or(self.max_delay).min(self.max_delay);
+        let half = delay / 2;
+        if half.as_millis() == 0 {
+            return delay;
+        }
+        half + jitter(half)
+    }
+}
+
+/// How a provider tells clients to slow down, besides `Retry-After`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitHeaders {
+    /// the remaining requests and the seconds until the reset in two
+    /// headers, e.g. `Ratelimit-Remaining` and `Ratelimit-Reset`
+    Split { remaining: &'static str, reset: &'static str },
+    /// both in one header, e.g. `Ratelimit: "default";r=0;t=30`
+    Combined(&'static str),
+}
+
+/// The headers of a provider api that [`RetryPolicy::execute`] has to know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderHeaders {
+    pub rate_limit: RateLimitHeaders,
+    /// lowercase names of the headers whose values are never logged
+    pub credentials: &'static [&'static str],
+}
+
+impl ProviderHeaders {
+    /// How long the server asked to wait in seconds, from `Retry-After`, or
+    /// from the rate limit reset once the remaining requests are used up.
+    pub fn retry_after(&self, headers: &HeaderMap) -> Option<u64> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let seconds = |name: &str| header(name).and_then(|value| value.trim().parse::<u64>().ok());
+        if let Some(seconds) = seconds("Retry-After") {
+            return Some(seconds);
+        }
+        match self.rate_limit {
+            RateLimitHeaders::Split { remaining, reset } => match seconds(remaining) {
+                Some(0) => seconds(reset),
+                _ => None,
+            },
+            RateLimitHeaders::Combined(name) => {
+                let params: Vec<(&str, &str)> = header(name)?
+                    .split(';')
+                    .filter_map(|param| param.trim().split_once('='))
+                    .collect();
+                let param = |name: &str| params.iter()
+                    .find(|(key, _)| *key == name)
+                    .and_then(|(_, value)| value.parse::<u64>().ok());
+                match param("r") {
+                    Some(0) => param("t"),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// The headers as name and value, with the values of the credentials replaced.
+    pub fn redacted_headers(&self, headers: &HeaderMap) -> Vec<(String, String)> {
+        headers.iter()
+            .map(|(name, value)| {
+                let value = if self.credentials.contains(&name.as_str()) {
+                    String::from("[redacted]")
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (String::from(name.as_str()), value)
+            })
+            .collect()
+    }
+}
+
+/// Whether a request with this method can be sent again without changing
+/// the result, creates are only retried if they were rate limited.
+fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS)
+}
+
+/// Statuses of errors that may be gone with the next attempt.
+fn is_transient(status: StatusCode) -> bool {
+    matches!(status, StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT)
+}
+
+/// Returns a random delay between zero and `max` in whole milliseconds, so
+/// requests of several clients don't happen at the same time.
+pub fn jitter(max: Duration) -> Duration {
+    let millis = max.as_millis() as u64;
+    if millis == 0 {
+        return Duration::from_millis(0);
+    }
+    // every RandomState is seeded differently, which is random enough for a jitter
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % (millis + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        let first = policy.delay(1, Duration::from_secs(0), None).unwrap();
+        assert!(first >= Duration::from_millis(250) && first <= Duration::from_millis(500));
+        let third = policy.delay(3, Duration::from_secs(0), None).unwrap();
+        assert!(third >= Duration::from_millis(1000) && third <= Duration::from_millis(2000));
+
+        let capped = RetryPolicy { max_attempts: 100, deadline: Duration::from_secs(3600), ..policy };
+        assert!(capped.delay(50, Duration::from_secs(0), None).unwrap() <= policy.max_delay);
+    }
+
+    #[test]
+    fn test_retry_after_split() {
+        let provider = ProviderHeaders {
+            rate_limit: RateLimitHeaders::Split { remaining: "Ratelimit-Remaining", reset: "Ratelimit-Reset" },
+            credentials: &[],
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("Ratelimit-Remaining", "0".parse().unwrap());
+        headers.insert("Ratelimit-Reset", "12".parse().unwrap());
+        assert_eq!(Some(12), provider.retry_after(&headers));
+        headers.insert("Retry-After", "3".parse().unwrap());
+        assert_eq!(Some(3), provider.retry_after(&headers));
+        headers.remove("Retry-After");
+        headers.insert("Ratelimit-Remaining", "5".parse().unwrap());
+        assert_eq!(None, provider.retry_after(&headers));
+    }
+
+    #[test]
+    fn test_retry_after_combined() {
+        let provider = ProviderHeaders { rate_limit: RateLimitHeaders::Combined("Ratelimit"), credentials: &[] };
+        let mut headers = HeaderMap::new();
+        headers.insert("Ratelimit", r#""default";r=0;t=30"#.parse().unwrap());
+        assert_eq!(Some(30), provider.retry_after(&headers));
+        headers.insert("Ratelimit", r#""default";r=12;t=30"#.parse().unwrap());
+        assert_eq!(None, provider.retry_after(&headers));
+        headers.insert("Retry-After", "5".parse().unwrap());
+        assert_eq!(Some(5), provider.retry_after(&headers));
+    }
+
+    #[test]
+    fn test_jitter() {
+        assert_eq!(Duration::from_millis(0), jitter(Duration::from_millis(0)));
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_secs(30)) <= Duration::from_secs(30));
+        }
+    }
+
+    #[test]
+    fn test_attempts_and_deadline() {
+        let policy = RetryPolicy::default();
+        assert_eq!(None, policy.delay(4, Duration::from_secs(0), None));
+        assert_eq!(None, RetryPolicy::never().delay(1, Duration::from_secs(0), None));
+        assert_eq!(Some(Duration::from_secs(5)), policy.delay(1, Duration::from_secs(0), Some(Duration::from_secs(5))));
+        assert_eq!(None, policy.delay(1, Duration::from_secs(58), Some(Duration::from_secs(5))));
+    }
+}
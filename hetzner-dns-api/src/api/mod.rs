@@ -1,13 +1,329 @@
-use reqwest::{Method, RequestBuilder};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::marker::PhantomData;
+use std::time::Duration;
+use dns_provider::{ProviderHeaders, RateLimitHeaders, RetryPolicy};
+use crate::HetznerError;
+
+pub const BASE_URL: &str = "https://dns.hetzner.com/api/v1";
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+/// default page size of the listings, the most hetzner allows for zones
+pub const PER_PAGE: u32 = 100;
+
+/// The rate limit and credential headers of the hetzner api.
+const HEADERS: ProviderHeaders = ProviderHeaders {
+    rate_limit: RateLimitHeaders::Split { remaining: "Ratelimit-Remaining", reset: "Ratelimit-Reset" },
+    credentials: &["auth-api-token"],
+};
 
 pub mod records;
+mod timestamp;
 pub mod zones;
 
-pub fn hetzner_client(url: &str, method: Method) -> RequestBuilder {
-    let token = env::var("HETZNER_TOKEN").unwrap();
-    reqwest::Client::new()
-        .request(method, url)
-        .header("Auth-API-Token", token.as_str())
+#[derive(Deserialize, Debug)]
+struct ErrorMessage {
+    message: String,
+}
+
+/// Hetzner answers errors either with `{"message": ...}` or `{"error": {"message": ...}}`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ErrorBody {
+    Nested { error: ErrorMessage },
+    Flat(ErrorMessage),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Meta {
+    pub pagination: Pagination,
+}
+
+/// Pagination of a listing, `page` starts at 1.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Pagination {
+    pub page: u32,
+    pub per_page: u32,
+    #[serde(default)]
+    pub last_page: u32,
+    #[serde(default)]
+    pub total_entries: u32,
+}
+
+impl Pagination {
+    pub fn is_last_page(&self) -> bool {
+        self.page >= self.last_page
+    }
+}
+
+/// A listing response that is split into pages.
+pub trait Paged: DeserializeOwned {
+    type Item;
+
+    /// Splits the response into its entries and the pagination.
+    fn into_page(self) -> (Vec<Self::Item>, Option<Pagination>);
+}
+
+/// Fetches the pages of a listing one after another, either page by page
+/// with `next_page` until it returns `None`, or all at once with `collect`.
+pub struct Pages<'a, P> {
+    client: &'a HetznerClient,
+    path: String,
+    query: Vec<(String, String)>,
+    page: Option<u32>,
+    per_page: u32,
+    listing: PhantomData<P>,
+}
+
+impl<'a, P: Paged> Pages<'a, P> {
+    fn new(client: &'a HetznerClient, path: &str, query: Vec<(String, String)>, per_page: u32) -> Self {
+        Pages {
+            client,
+            path: String::from(path),
+            query,
+            page: Some(1),
+            per_page,
+            listing: PhantomData,
+        }
+    }
+
+    /// Returns the entries of the next page, or `None` after the last page or an error.
+    pub async fn next_page(&mut self) -> Option<Result<Vec<P::Item>, HetznerError>> {
+        let page = self.page?;
+        let client = self.client.request(self.path.as_str(), Method::GET)
+            .query(&self.query)
+            .query(&[("page", page), ("per_page", self.per_page)]);
+        match self.client.send::<P>(client).await {
+            Ok(listing) => {
+                let (items, pagination) = listing.into_page();
+                self.page = match pagination {
+                    Some(pagination) if !items.is_empty() && !pagination.is_last_page() => Some(page + 1),
+                    _ => None,
+                };
+                Some(Ok(items))
+            }
+            Err(e) => {
+                self.page = None;
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// Fetches all remaining pages.
+    pub async fn collect(mut self) -> Result<Vec<P::Item>, HetznerError> {
+        let mut items = Vec::new();
+        while let Some(page) = self.next_page().await {
+            items.extend(page?);
+        }
+        Ok(items)
+    }
+}
+
+/// Client of the hetzner dns api.
+///
+/// Clones share the same connection pool, so one client per account is enough.
+#[derive(Clone)]
+pub struct HetznerClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+    per_page: u32,
+    retry: RetryPolicy,
+}
+
+pub struct HetznerClientBuilder {
+    token: String,
+    base_url: String,
+    timeout: Duration,
+    per_page: u32,
+    retry: RetryPolicy,
+}
+
+impl HetznerClientBuilder {
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = String::from(base_url.trim_end_matches('/'));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Page size used by the listings that fetch all pages.
+    pub fn per_page(mut self, per_page: u32) -> Self {
+        self.per_page = per_page;
+        self
+    }
+
+    /// Retries of rate limited and failed requests, see [`RetryPolicy`].
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<HetznerClient, HetznerError> {
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()?;
+        Ok(HetznerClient {
+            client,
+            base_url: self.base_url,
+            token: self.token,
+            per_page: self.per_page,
+            retry: self.retry,
+        })
+    }
+}
+
+impl HetznerClient {
+    pub fn builder(token: &str) -> HetznerClientBuilder {
+        HetznerClientBuilder {
+            token: String::from(token),
+            base_url: String::from(BASE_URL),
+            timeout: TIMEOUT,
+            per_page: PER_PAGE,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn new(token: &str) -> Result<HetznerClient, HetznerError> {
+        HetznerClient::builder(token).build()
+    }
+
+    /// Creates a client with the token of the env `HETZNER_TOKEN`.
+    pub fn from_env() -> Result<HetznerClient, HetznerError> {
+        HetznerClient::builder_from_env()?.build()
+    }
+
+    /// Starts a client with the token of the env `HETZNER_TOKEN`.
+    pub fn builder_from_env() -> Result<HetznerClientBuilder, HetznerError> {
+        let token = env::var("HETZNER_TOKEN")
+            .map_err(|_| HetznerError::Auth(String::from("HETZNER_TOKEN is not set")))?;
+        Ok(HetznerClient::builder(token.as_str()))
+    }
+
+    pub fn request(&self, path: &str, method: Method) -> RequestBuilder {
+        let url = format!("{}/{}", self.base_url, path);
+        self.client
+            .request(method, url)
+            .header("Auth-API-Token", self.token.as_str())
+    }
+
+    /// Sends the request and decodes the json body of a successful response.
+    pub async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, HetznerError> {
+        let response = check_status(self.retry.execute(&HEADERS, request).await?).await?;
+        let body = response.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Sends the request and returns the body of a successful response as text.
+    pub async fn send_text(&self, request: RequestBuilder) -> Result<String, HetznerError> {
+        let response = check_status(self.retry.execute(&HEADERS, request).await?).await?;
+        Ok(response.text().await?)
+    }
+
+    /// Sends the request and only checks the status, for responses without body.
+    pub async fn send_empty(&self, request: RequestBuilder) -> Result<(), HetznerError> {
+        check_status(self.retry.execute(&HEADERS, request).await?).await?;
+        Ok(())
+    }
+
+    /// Pages through the listing at `path`, see [`Pages`].
+    pub fn pages<P: Paged>(&self, path: &str, query: Vec<(String, String)>, per_page: u32) -> Pages<'_, P> {
+        Pages::new(self, path, query, per_page)
+    }
+}
+
+async fn check_status(response: Response) -> Result<Response, HetznerError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = HEADERS.retry_after(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<ErrorBody>(body.as_str()) {
+        Ok(ErrorBody::Nested { error }) => error.message,
+        Ok(ErrorBody::Flat(error)) => error.message,
+        Err(_) => body,
+    };
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => HetznerError::Auth(message),
+        StatusCode::NOT_FOUND => HetznerError::NotFound(message),
+        StatusCode::TOO_MANY_REQUESTS => HetznerError::RateLimited { retry_after },
+        _ => HetznerError::Status { status: status.as_u16(), message },
+    })
+}
+
+#[cfg(test)]
+pub fn test_client() -> HetznerClient {
+    HetznerClient::builder("token")
+        .base_url(&mockito::server_url())
+        .retry(RetryPolicy::never())
+        .build()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use tokio::runtime::Runtime;
+
+    fn retry_client() -> HetznerClient {
+        let retry = RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        HetznerClient::builder("token")
+            .base_url(&mockito::server_url())
+            .retry(retry)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_retry_transient_errors() {
+        let failed = mock("GET", "/zones")
+            .match_query(Matcher::UrlEncoded(String::from("name"), String::from("retry.example")))
+            .with_status(502)
+            .expect(2)
+            .create();
+        let succeeded = mock("GET", "/zones")
+            .match_query(Matcher::UrlEncoded(String::from("name"), String::from("retry.example")))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"zones": [], "meta": {"pagination": {"page": 1, "per_page": 100, "last_page": 1}}}"#)
+            .expect(1)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let zones = retry_client().get_zones(Some("retry.example")).await.unwrap();
+            assert!(zones.zones.is_empty());
+        });
+        failed.assert();
+        succeeded.assert();
+    }
+
+    #[test]
+    fn test_no_retry_of_failed_create() {
+        let m = mock("POST", "/records")
+            .match_body(Matcher::PartialJsonString(String::from(r#"{"name": "no-retry"}"#)))
+            .with_status(502)
+            .expect(1)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = retry_client().create_record("no-retry", records::RecordType::A, "192.0.2.1", None, String::from("1-1-1-1")).await.err().unwrap();
+            assert!(matches!(error, HetznerError::Status { status: 502, .. }));
+        });
+        m.assert();
+    }
+
+    #[test]
+    fn test_redacted_headers() {
+        let request = test_client().request("zones", Method::GET).build().unwrap();
+        let headers = HEADERS.redacted_headers(request.headers());
+        assert_eq!(vec![(String::from("auth-api-token"), String::from("[redacted]"))], headers);
+    }
 }
@@ -1,65 +1,388 @@
 use super::*;
-use reqwest::Method;
+use chrono::{DateTime, Utc};
 
-#[derive(Deserialize, Debug)]
+/// Type of a record, types hetzner adds later are kept as [`RecordType::Other`].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Caa,
+    Cname,
+    Dane,
+    Ds,
+    Hinfo,
+    Mx,
+    Ns,
+    Ptr,
+    Rp,
+    Soa,
+    Srv,
+    Tlsa,
+    Txt,
+    Other(String),
+}
+
+impl RecordType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Caa => "CAA",
+            RecordType::Cname => "CNAME",
+            RecordType::Dane => "DANE",
+            RecordType::Ds => "DS",
+            RecordType::Hinfo => "HINFO",
+            RecordType::Mx => "MX",
+            RecordType::Ns => "NS",
+            RecordType::Ptr => "PTR",
+            RecordType::Rp => "RP",
+            RecordType::Soa => "SOA",
+            RecordType::Srv => "SRV",
+            RecordType::Tlsa => "TLSA",
+            RecordType::Txt => "TXT",
+            RecordType::Other(name) => name.as_str(),
+        }
+    }
+}
+
+impl From<&str> for RecordType {
+    fn from(value: &str) -> Self {
+        match value.to_ascii_uppercase().as_str() {
+            "A" => RecordType::A,
+            "AAAA" => RecordType::Aaaa,
+            "CAA" => RecordType::Caa,
+            "CNAME" => RecordType::Cname,
+            "DANE" => RecordType::Dane,
+            "DS" => RecordType::Ds,
+            "HINFO" => RecordType::Hinfo,
+            "MX" => RecordType::Mx,
+            "NS" => RecordType::Ns,
+            "PTR" => RecordType::Ptr,
+            "RP" => RecordType::Rp,
+            "SOA" => RecordType::Soa,
+            "SRV" => RecordType::Srv,
+            "TLSA" => RecordType::Tlsa,
+            "TXT" => RecordType::Txt,
+            _ => RecordType::Other(String::from(value)),
+        }
+    }
+}
+
+impl From<String> for RecordType {
+    fn from(value: String) -> Self {
+        RecordType::from(value.as_str())
+    }
+}
+
+impl From<RecordType> for String {
+    fn from(record_type: RecordType) -> Self {
+        String::from(record_type.as_str())
+    }
+}
+
+impl std::fmt::Display for RecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Record {
     #[serde(rename="type")]
-    pub record_type: String,
+    pub record_type: RecordType,
     pub id: String,
-    pub created: String,
-    pub modified: String,
+    #[serde(with = "timestamp")]
+    pub created: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub modified: DateTime<Utc>,
     pub zone_id: String,
     pub name: String,
     pub value: String,
+    /// seconds, `None` uses the ttl of the zone
+    pub ttl: Option<u32>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct RecordCreate {
     #[serde(rename="type")]
-    pub record_type: String,
+    pub record_type: RecordType,
+    #[serde(default)]
     pub zone_id: String,
     pub name: String,
     pub value: String,
+    /// seconds, `None` uses the ttl of the zone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Records {
-    pub records: Vec<Record>
-}
-
-pub async fn create_record(name: &str, record_type: &str, value: &str, zone_id: String) -> String {
-    let new_record = RecordCreate {
-        record_type: String::from(record_type),
-        value: String::from(value),
-        name: String::from(name),
-        zone_id: String::from(zone_id),
-    };
-    let client = hetzner_client("https://dns.hetzner.com/api/v1/records", Method::POST)
-        .json(&new_record);
-    client.send().await.unwrap().text().await.unwrap()
-}
-
-pub async fn delete_record(record_id: &str) {
-    let url = format!("{}{}", "https://dns.hetzner.com/api/v1/records/", String::from(record_id));
-    let client = hetzner_client(&*url, Method::DELETE);
-    client.send().await.unwrap().status();
-}
-
-pub async fn get_all_records(zone_id: String) -> Records {
-    let client = hetzner_client("https://dns.hetzner.com/api/v1/records", Method::GET)
-        .query(&[("zone_id", zone_id)]);
-    client.send().await.unwrap().json::<Records>().await.unwrap()
-}
-
-pub async fn update_record(id: &str, name: &str, record_type: &str, value: &str, zone_id: String) -> String {
-    let url = format!("{}{}", "https://dns.hetzner.com/api/v1/records/", id);
-    let new_record = RecordCreate {
-        record_type: String::from(record_type),
-        value: String::from(value),
-        name: String::from(name),
-        zone_id: String::from(zone_id),
-    };
-    let client = hetzner_client(url.as_str(), Method::PUT)
-        .json(&new_record);
-    client.send().await.unwrap().text().await.unwrap()
-}
\ No newline at end of file
+    pub records: Vec<Record>,
+    pub meta: Option<Meta>,
+}
+
+impl Paged for Records {
+    type Item = Record;
+
+    fn into_page(self) -> (Vec<Record>, Option<Pagination>) {
+        (self.records, self.meta.map(|meta| meta.pagination))
+    }
+}
+
+/// A record of a bulk update, the fields replace all fields of the record.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RecordUpdate {
+    pub id: String,
+    #[serde(flatten)]
+    pub record: RecordCreate,
+}
+
+#[derive(Serialize)]
+struct BulkRecords<'a, T> {
+    records: &'a [T],
+}
+
+/// Result of a bulk create, the valid records are created even if others are invalid.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BulkCreated {
+    #[serde(default)]
+    pub records: Vec<Record>,
+    #[serde(default)]
+    pub valid_records: Vec<RecordCreate>,
+    #[serde(default)]
+    pub invalid_records: Vec<RecordCreate>,
+}
+
+/// Result of a bulk update, the other records are updated even if some failed.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BulkUpdated {
+    #[serde(default)]
+    pub records: Vec<Record>,
+    #[serde(default)]
+    pub failed_records: Vec<RecordCreate>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RecordResponse {
+    pub record: Record
+}
+
+impl HetznerClient {
+    pub async fn create_record(&self, name: &str, record_type: RecordType, value: &str, ttl: Option<u32>, zone_id: String) -> Result<Record, HetznerError> {
+        let new_record = RecordCreate {
+            record_type,
+            value: String::from(value),
+            name: String::from(name),
+            zone_id,
+            ttl,
+        };
+        let client = self.request("records", Method::POST)
+            .json(&new_record);
+        Ok(self.send::<RecordResponse>(client).await?.record)
+    }
+
+    /// Creates several records with one request.
+    ///
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/BulkCreateRecords)
+    pub async fn create_records(&self, records: &[RecordCreate]) -> Result<BulkCreated, HetznerError> {
+        let client = self.request("records/bulk", Method::POST)
+            .json(&BulkRecords { records });
+        self.send(client).await
+    }
+
+    /// Updates several records with one request.
+    ///
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/BulkUpdateRecords)
+    pub async fn update_records(&self, records: &[RecordUpdate]) -> Result<BulkUpdated, HetznerError> {
+        let client = self.request("records/bulk", Method::PUT)
+            .json(&BulkRecords { records });
+        self.send(client).await
+    }
+
+    pub async fn delete_record(&self, record_id: &str) -> Result<(), HetznerError> {
+        let url = format!("records/{}", record_id);
+        let client = self.request(&url, Method::DELETE);
+        self.send_empty(client).await
+    }
+
+    /// Fetches the records of all pages, see [`HetznerClientBuilder::per_page`].
+    pub async fn get_all_records(&self, zone_id: String) -> Result<Records, HetznerError> {
+        let records = self.records_pages(zone_id.as_str(), self.per_page).collect().await?;
+        Ok(Records { records, meta: None })
+    }
+
+    /// Pages through the records of the zone.
+    pub fn records_pages(&self, zone_id: &str, per_page: u32) -> Pages<'_, Records> {
+        self.pages("records", vec![(String::from("zone_id"), String::from(zone_id))], per_page)
+    }
+
+    /// Returns the records of the zone with this name and type.
+    ///
+    /// The api can only filter records by zone, so the name and type are
+    /// matched while paging, without keeping the other records around.
+    pub async fn get_records_by_name(&self, zone_id: &str, name: &str, record_type: &RecordType) -> Result<Vec<Record>, HetznerError> {
+        let mut matching = Vec::new();
+        let mut pages = self.records_pages(zone_id, self.per_page);
+        while let Some(records) = pages.next_page().await {
+            matching.extend(records?.into_iter().filter(|record| {
+                record.name.eq_ignore_ascii_case(name) && record.record_type == *record_type
+            }));
+        }
+        Ok(matching)
+    }
+
+    pub async fn update_record(&self, id: &str, name: &str, record_type: RecordType, value: &str, ttl: Option<u32>, zone_id: String) -> Result<Record, HetznerError> {
+        let url = format!("records/{}", id);
+        let new_record = RecordCreate {
+            record_type,
+            value: String::from(value),
+            name: String::from(name),
+            zone_id,
+            ttl,
+        };
+        let client = self.request(url.as_str(), Method::PUT)
+            .json(&new_record);
+        Ok(self.send::<RecordResponse>(client).await?.record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use mockito::{mock, Matcher};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_get_all_records() {
+        let _m = mock("GET", "/records")
+            .match_query(Matcher::UrlEncoded(String::from("zone_id"), String::from("1-1-1-1")))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "records": [
+                    {
+                        "id": "2-2-2-2",
+                        "type": "A",
+                        "name": "home",
+                        "value": "203.0.113.7",
+                        "ttl": 300,
+                        "zone_id": "1-1-1-1",
+                        "created": "2021-05-20 10:00:00.000 +0000 UTC",
+                        "modified": "2021-05-20 10:00:00.000 +0000 UTC"
+                    }
+                ]
+            }"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let records = test_client().get_all_records(String::from("1-1-1-1")).await.unwrap();
+            assert_eq!(1, records.records.len());
+            let record = records.records.first().unwrap();
+            assert_eq!("2-2-2-2", record.id.as_str());
+            assert_eq!("203.0.113.7", record.value.as_str());
+            assert_eq!(Some(300), record.ttl);
+        });
+    }
+
+    #[test]
+    fn test_delete_unknown_record() {
+        let _m = mock("DELETE", "/records/3-3-3-3")
+            .with_status(404)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"error": {"message": "record not found", "code": 404}}"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = test_client().delete_record("3-3-3-3").await.err().unwrap();
+            assert!(matches!(error, HetznerError::NotFound(_)));
+            assert_eq!("Not found: record not found", format!("{}", error));
+        });
+    }
+
+    #[test]
+    fn test_update_record_server_error() {
+        let _m = mock("PUT", "/records/2-2-2-2")
+            .with_status(502)
+            .with_body("Bad Gateway")
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let result = test_client().update_record("2-2-2-2", "home", RecordType::A, "203.0.113.7", None, String::from("1-1-1-1")).await;
+            let error = result.err().unwrap();
+            assert!(matches!(error, HetznerError::Status { status: 502, .. }));
+            assert_eq!("Request failed with status 502: Bad Gateway", format!("{}", error));
+        });
+    }
+
+    #[test]
+    fn test_get_records_of_all_pages() {
+        let record = |id: &str, name: &str, record_type: &str| format!(r#"{{
+            "id": "{}", "type": "{}", "name": "{}", "value": "192.0.2.1", "zone_id": "4-4-4-4",
+            "created": "2021-05-20 10:00:00.000 +0000 UTC", "modified": "2021-05-20 10:00:00.000 +0000 UTC"
+        }}"#, id, record_type, name);
+        let page = |page: &str| Matcher::AllOf(vec![
+            Matcher::UrlEncoded(String::from("zone_id"), String::from("4-4-4-4")),
+            Matcher::UrlEncoded(String::from("page"), String::from(page)),
+            Matcher::UrlEncoded(String::from("per_page"), String::from("2")),
+        ]);
+        let _first = mock("GET", "/records")
+            .match_query(page("1"))
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{
+                "records": [{}, {}],
+                "meta": {{"pagination": {{"page": 1, "per_page": 2, "last_page": 2, "total_entries": 3}}}}
+            }}"#, record("1", "home", "A"), record("2", "home", "AAAA")))
+            .create();
+        let _second = mock("GET", "/records")
+            .match_query(page("2"))
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{
+                "records": [{}],
+                "meta": {{"pagination": {{"page": 2, "per_page": 2, "last_page": 2, "total_entries": 3}}}}
+            }}"#, record("3", "vpn", "A")))
+            .create();
+        let client = HetznerClient::builder("token")
+            .base_url(&mockito::server_url())
+            .per_page(2)
+            .build()
+            .unwrap();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let records = client.get_all_records(String::from("4-4-4-4")).await.unwrap();
+            let ids: Vec<&str> = records.records.iter().map(|record| record.id.as_str()).collect();
+            assert_eq!(vec!["1", "2", "3"], ids);
+
+            let records = client.get_records_by_name("4-4-4-4", "home", &RecordType::Aaaa).await.unwrap();
+            assert_eq!(1, records.len());
+            assert_eq!("2", records[0].id.as_str());
+
+            let mut pages = client.records_pages("4-4-4-4", 2);
+            assert_eq!(2, pages.next_page().await.unwrap().unwrap().len());
+            assert_eq!(1, pages.next_page().await.unwrap().unwrap().len());
+            assert!(pages.next_page().await.is_none());
+        });
+    }
+
+    #[test]
+    fn test_record_types_and_timestamps() {
+        let record: Record = serde_json::from_str(r#"{
+            "id": "5-5-5-5", "type": "LOC", "name": "office", "value": "52 31 0 N 13 24 0 E 30m",
+            "ttl": 86400, "zone_id": "1-1-1-1",
+            "created": "2021-05-20 10:00:00.000 +0000 UTC", "modified": "2021-05-21 12:30:00.5 +0200 CEST"
+        }"#).unwrap();
+        assert_eq!(RecordType::Other(String::from("LOC")), record.record_type);
+        assert_eq!(Some(86400), record.ttl);
+        assert_eq!(Utc.with_ymd_and_hms(2021, 5, 21, 10, 30, 0).unwrap() + chrono::Duration::milliseconds(500), record.modified);
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!("LOC", json["type"]);
+        assert_eq!("2021-05-20 10:00:00.000 +0000 UTC", json["created"]);
+        assert_eq!(record, serde_json::from_value::<Record>(json).unwrap());
+
+        assert_eq!(RecordType::Mx, RecordType::from("mx"));
+        assert_eq!("AAAA", RecordType::Aaaa.to_string());
+    }
+}
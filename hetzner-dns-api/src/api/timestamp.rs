@@ -0,0 +1,44 @@
+//! (De)serializes the timestamps of hetzner, e.g. `2021-05-20 10:00:00.000 +0000 UTC`,
+//! for use with `#[serde(with = "timestamp")]`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+const FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f %z";
+
+pub fn serialize<S: Serializer>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(format!("{} UTC", timestamp.format("%Y-%m-%d %H:%M:%S%.3f %z")).as_str())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse(value.as_str()).map_err(serde::de::Error::custom)
+}
+
+/// Parses the format of hetzner, the trailing zone name is only informative
+/// as the offset is part of the timestamp. RFC 3339 is accepted as well.
+pub fn parse(value: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    let trimmed = value.trim();
+    let without_zone = match trimmed.rsplit_once(' ') {
+        Some((timestamp, zone)) if zone.chars().all(|c| c.is_ascii_alphabetic()) => timestamp,
+        _ => trimmed,
+    };
+    DateTime::parse_from_str(without_zone, FORMAT)
+        .or_else(|_| DateTime::parse_from_rfc3339(trimmed))
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse() {
+        let expected = Utc.with_ymd_and_hms(2021, 5, 20, 10, 0, 0).unwrap();
+        assert_eq!(expected, parse("2021-05-20 10:00:00.000 +0000 UTC").unwrap());
+        assert_eq!(expected, parse("2021-05-20 12:00:00 +0200 CEST").unwrap());
+        assert_eq!(expected, parse("2021-05-20T10:00:00Z").unwrap());
+        assert!(parse("20.05.2021").is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use super::*;
-use reqwest::Method;
+use super::records::Record;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Zone {
     pub id: String,
     pub name: String,
@@ -22,15 +22,309 @@ pub struct Zone {
     pub records_count: i32,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Zones {
-    pub zones: Vec<Zone>
+    pub zones: Vec<Zone>,
+    pub meta: Option<Meta>,
 }
 
-pub async fn get_zones(name: Option<&str>) -> Result<Zones, Box<dyn std::error::Error>> {
-    let mut client = hetzner_client("https://dns.hetzner.com/api/v1/zones", Method::GET);
-    if name.is_some() {
-        client = client.query(&[("name", name.unwrap())]);
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ZoneResponse {
+    pub zone: Zone,
+}
+
+/// Name and default ttl of a new zone, or the new values of an existing one.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ZoneCreate {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<i32>,
+}
+
+/// Result of validating a zone file, see [`HetznerClient::validate_zone_file`].
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ZoneFileValidation {
+    pub parsed_records: i32,
+    #[serde(default)]
+    pub valid_records: Vec<Record>,
+    /// records that are valid, but would be replaced by the records of hetzner, e.g. SOA
+    #[serde(default)]
+    pub passed_records: Vec<Record>,
+}
+
+impl Paged for Zones {
+    type Item = Zone;
+
+    fn into_page(self) -> (Vec<Zone>, Option<Pagination>) {
+        (self.zones, self.meta.map(|meta| meta.pagination))
+    }
+}
+
+impl HetznerClient {
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/GetZones)
+    ///
+    /// Fetches the zones of all pages, see [`HetznerClientBuilder::per_page`].
+    ///
+    /// # Arguments
+    /// * `name` - filter by zone-name, e.g. example.com
+    ///
+    pub async fn get_zones(&self, name: Option<&str>) -> Result<Zones, HetznerError> {
+        let zones = self.zones_pages(name, self.per_page).collect().await?;
+        Ok(Zones { zones, meta: None })
+    }
+
+    /// Pages through the zones, filtered by zone-name like [`HetznerClient::get_zones`].
+    pub fn zones_pages(&self, name: Option<&str>, per_page: u32) -> Pages<'_, Zones> {
+        let query = name.map(|name| vec![(String::from("name"), String::from(name))]).unwrap_or_default();
+        self.pages("zones", query, per_page)
+    }
+
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/GetZone)
+    pub async fn get_zone(&self, zone_id: &str) -> Result<Zone, HetznerError> {
+        let url = format!("zones/{}", zone_id);
+        let client = self.request(url.as_str(), Method::GET);
+        Ok(self.send::<ZoneResponse>(client).await?.zone)
+    }
+
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/CreateZone)
+    pub async fn create_zone(&self, name: &str, ttl: Option<i32>) -> Result<Zone, HetznerError> {
+        let zone = ZoneCreate { name: String::from(name), ttl };
+        let client = self.request("zones", Method::POST).json(&zone);
+        Ok(self.send::<ZoneResponse>(client).await?.zone)
+    }
+
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/UpdateZone)
+    pub async fn update_zone(&self, zone_id: &str, name: &str, ttl: Option<i32>) -> Result<Zone, HetznerError> {
+        let url = format!("zones/{}", zone_id);
+        let zone = ZoneCreate { name: String::from(name), ttl };
+        let client = self.request(url.as_str(), Method::PUT).json(&zone);
+        Ok(self.send::<ZoneResponse>(client).await?.zone)
+    }
+
+    /// Deletes the zone with all of its records.
+    ///
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/DeleteZone)
+    pub async fn delete_zone(&self, zone_id: &str) -> Result<(), HetznerError> {
+        let url = format!("zones/{}", zone_id);
+        let client = self.request(url.as_str(), Method::DELETE);
+        self.send_empty(client).await
+    }
+
+    /// Replaces the records of the zone with the records of a zone file in
+    /// BIND format.
+    ///
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/ImportZoneFilePlain)
+    pub async fn import_zone_file(&self, zone_id: &str, zone_file: &str) -> Result<Zone, HetznerError> {
+        let url = format!("zones/{}/import", zone_id);
+        let client = self.request(url.as_str(), Method::POST)
+            .header("Content-Type", "text/plain")
+            .body(String::from(zone_file));
+        Ok(self.send::<ZoneResponse>(client).await?.zone)
+    }
+
+    /// Returns the records of the zone as a zone file in BIND format.
+    ///
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/ExportZoneFile)
+    pub async fn export_zone_file(&self, zone_id: &str) -> Result<String, HetznerError> {
+        let url = format!("zones/{}/export", zone_id);
+        let client = self.request(url.as_str(), Method::GET);
+        self.send_text(client).await
+    }
+
+    /// Parses a zone file in BIND format without importing it.
+    ///
+    /// # Links
+    /// [see hetzner documentation](https://dns.hetzner.com/api-docs#operation/ValidateZoneFilePlain)
+    pub async fn validate_zone_file(&self, zone_file: &str) -> Result<ZoneFileValidation, HetznerError> {
+        let client = self.request("zones/file/validate", Method::POST)
+            .header("Content-Type", "text/plain")
+            .body(String::from(zone_file));
+        self.send(client).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_get_zones_successfully() {
+        let _m = mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .match_header("Auth-API-Token", "token")
+            .with_header("content-type", "application/json")
+            .with_body(r#"
+            {
+                "zones": [
+                    {
+                        "id": "1-1-1-1",
+                        "name": "example.com",
+                        "ttl": 86400,
+                        "registrar": "",
+                        "legacy_dns_host": "",
+                        "legacy_ns": [],
+                        "ns": ["hydrogen.ns.hetzner.com"],
+                        "created": "2021-05-20 10:00:00.000 +0000 UTC",
+                        "verified": "",
+                        "modified": "2021-05-20 10:00:00.000 +0000 UTC",
+                        "project": "",
+                        "owner": "",
+                        "permission": "",
+                        "status": "verified",
+                        "paused": false,
+                        "is_secondary_dns": false,
+                        "records_count": 2
+                    }
+                ]
+            }
+            "#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let zones = test_client().get_zones(Option::None).await.unwrap();
+            assert_eq!(1, zones.zones.len());
+            assert_eq!("example.com", zones.zones[0].name.as_str());
+        });
+    }
+
+    #[test]
+    fn test_get_zones_unauthorized() {
+        let _m = mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .match_header("Auth-API-Token", "token")
+            .with_status(401)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"message": "Invalid authentication credentials"}"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = test_client().get_zones(Option::None).await.err().unwrap();
+            assert!(matches!(error, HetznerError::Auth(_)));
+            assert_eq!("Authentication failed: Invalid authentication credentials", format!("{}", error));
+        });
+    }
+
+    #[test]
+    fn test_get_zones_rate_limited() {
+        let _m = mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .match_header("Auth-API-Token", "token")
+            .with_status(429)
+            .with_header("Retry-After", "30")
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = test_client().get_zones(Option::None).await.err().unwrap();
+            assert!(matches!(error, HetznerError::RateLimited { retry_after: Some(30) }));
+        });
+    }
+
+    #[test]
+    fn test_get_zones_invalid_body() {
+        let _m = mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .match_header("Auth-API-Token", "token")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"zones": "none"}"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let error = test_client().get_zones(Option::None).await.err().unwrap();
+            assert!(matches!(error, HetznerError::Decode(_)));
+        });
+    }
+
+    fn zone_json(id: &str, name: &str, ttl: i32) -> String {
+        format!(r#"{{"zone": {{
+            "id": "{}", "name": "{}", "ttl": {}, "registrar": "", "legacy_dns_host": "", "legacy_ns": [],
+            "ns": ["hydrogen.ns.hetzner.com"], "created": "2021-05-20 10:00:00.000 +0000 UTC", "verified": "",
+            "modified": "2021-05-20 10:00:00.000 +0000 UTC", "project": "", "owner": "", "permission": "",
+            "status": "verified", "paused": false, "is_secondary_dns": false, "records_count": 0
+        }}}}"#, id, name, ttl)
+    }
+
+    #[test]
+    fn test_create_and_update_zone() {
+        let _create = mock("POST", "/zones")
+            .match_body(Matcher::Json(serde_json::json!({"name": "new.example", "ttl": 3600})))
+            .with_header("content-type", "application/json")
+            .with_body(zone_json("5-5-5-5", "new.example", 3600))
+            .create();
+        let _update = mock("PUT", "/zones/5-5-5-5")
+            .match_body(Matcher::Json(serde_json::json!({"name": "new.example"})))
+            .with_header("content-type", "application/json")
+            .with_body(zone_json("5-5-5-5", "new.example", 86400))
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let client = test_client();
+            let zone = client.create_zone("new.example", Some(3600)).await.unwrap();
+            assert_eq!("5-5-5-5", zone.id.as_str());
+            assert_eq!(3600, zone.ttl);
+            let zone = client.update_zone("5-5-5-5", "new.example", None).await.unwrap();
+            assert_eq!(86400, zone.ttl);
+        });
+    }
+
+    #[test]
+    fn test_import_and_export_zone_file() {
+        let zone_file = "$ORIGIN new.example.\nhome 300 IN A 192.0.2.1\n";
+        let _import = mock("POST", "/zones/6-6-6-6/import")
+            .match_header("content-type", "text/plain")
+            .match_body(zone_file)
+            .with_header("content-type", "application/json")
+            .with_body(zone_json("6-6-6-6", "new.example", 86400))
+            .create();
+        let _export = mock("GET", "/zones/6-6-6-6/export")
+            .with_header("content-type", "text/plain")
+            .with_body(zone_file)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let client = test_client();
+            assert_eq!("new.example", client.import_zone_file("6-6-6-6", zone_file).await.unwrap().name.as_str());
+            assert_eq!(zone_file, client.export_zone_file("6-6-6-6").await.unwrap());
+        });
+    }
+
+    #[test]
+    fn test_validate_zone_file() {
+        let _m = mock("POST", "/zones/file/validate")
+            .match_header("content-type", "text/plain")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "parsed_records": 2,
+                "valid_records": [{
+                    "id": "", "type": "A", "name": "home", "value": "192.0.2.1", "ttl": 300, "zone_id": "",
+                    "created": "2021-05-20 10:00:00.000 +0000 UTC", "modified": "2021-05-20 10:00:00.000 +0000 UTC"
+                }],
+                "passed_records": []
+            }"#)
+            .create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let validation = test_client().validate_zone_file("home 300 IN A 192.0.2.1\n").await.unwrap();
+            assert_eq!(2, validation.parsed_records);
+            assert_eq!("home", validation.valid_records[0].name.as_str());
+        });
     }
-    Ok(client.send().await?.json::<Zones>().await?)
-}
\ No newline at end of file
+
+    #[test]
+    fn test_delete_zone() {
+        let m = mock("DELETE", "/zones/7-7-7-7").expect(1).create();
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            test_client().delete_zone("7-7-7-7").await.unwrap();
+        });
+        m.assert();
+    }
+}
@@ -1,46 +1,71 @@
 mod api;
+mod provider;
 
-use api::zones::*;
-use api::records::*;
+use std::error::Error;
+use std::fmt::Formatter;
+pub use api::{HetznerClient, HetznerClientBuilder, Meta, Paged, Pages, Pagination};
+pub use api::records::{BulkCreated, BulkUpdated, Record, RecordCreate, RecordResponse, RecordType, RecordUpdate, Records};
+pub use api::zones::{Zone, ZoneCreate, ZoneFileValidation, ZoneResponse, Zones};
 
-pub async fn get_zone_by_name(name: &str) -> Zone {
-    let zones = api::zones::get_zones(Option::from(name)).await.unwrap();
-    zones.zones.into_iter().next().unwrap()
+#[derive(Debug)]
+pub enum HetznerError {
+    /// `HETZNER_TOKEN` is missing or was rejected (401/403)
+    Auth(String),
+    /// the zone or record does not exist (404)
+    NotFound(String),
+    /// too many requests (429), `retry_after` is in seconds
+    RateLimited { retry_after: Option<u64> },
+    /// any other unsuccessful status
+    Status { status: u16, message: String },
+    /// the response body could not be decoded
+    Decode(serde_json::Error),
+    /// the request could not be sent or the response could not be read
+    Transport(reqwest::Error),
 }
 
-pub async fn get_all_records_by_name(zone_name: &str) -> Records {
-    let zone = get_zone_by_name(zone_name).await;
-    get_all_records(zone.id).await
+impl std::fmt::Display for HetznerError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            HetznerError::Auth(message) => write!(f, "Authentication failed: {}", message),
+            HetznerError::NotFound(message) => write!(f, "Not found: {}", message),
+            HetznerError::RateLimited { retry_after: Some(seconds) } => write!(f, "Rate limited, retry after {} seconds", seconds),
+            HetznerError::RateLimited { retry_after: None } => write!(f, "Rate limited"),
+            HetznerError::Status { status, message } => write!(f, "Request failed with status {}: {}", status, message),
+            HetznerError::Decode(e) => write!(f, "Could not decode response: {}", e),
+            HetznerError::Transport(e) => write!(f, "Request failed: {}", e),
+        }
+    }
+}
+
+impl Error for HetznerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HetznerError::Decode(e) => Some(e),
+            HetznerError::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
-pub async fn delete_records_by_name(zone: &str, record_name: &str) {
-    let data = get_all_records_by_name(zone)
-        .await
-        .records
-        .into_iter()
-        .filter(|record| record.name == record_name);
-    for record in data {
-        delete_record(record.id.as_str()).await;
+impl From<reqwest::Error> for HetznerError {
+    fn from(error: reqwest::Error) -> Self {
+        HetznerError::Transport(error)
     }
 }
 
-pub async fn create_update_record(zone_name: &str, record_name: &str, value: &str, record_type: &str) {
-    let count = get_all_records_by_name(zone_name)
-        .await
-        .records
-        .into_iter()
-        .filter(|record| record.name == record_name).count();
-    if count > 1 {
-        delete_records_by_name(zone_name, record_name).await;
+impl From<serde_json::Error> for HetznerError {
+    fn from(error: serde_json::Error) -> Self {
+        HetznerError::Decode(error)
     }
-    if count == 1 {
-        let mut data = get_all_records_by_name(zone_name).await.records.into_iter()
-            .filter(|record| record.name == record_name);
-        let record = data.next().unwrap();
-        let zone = get_zone_by_name(zone_name).await;
-        update_record(record.id.as_str(), record_name, record_type, value, zone.id).await;
-        return;
+}
+
+impl HetznerClient {
+    /// Returns the zone with the given name, or [`HetznerError::NotFound`].
+    pub async fn find_zone(&self, name: &str) -> Result<Zone, HetznerError> {
+        let zones = self.get_zones(Option::from(name)).await?;
+        match zones.zones.into_iter().next() {
+            None => Err(HetznerError::NotFound(format!("No Zone found for name {}.", name))),
+            Some(zone) => Ok(zone)
+        }
     }
-    let zone = get_zone_by_name(zone_name).await;
-    create_record(record_name, record_type, value, zone.id).await;
 }
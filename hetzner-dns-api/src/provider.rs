@@ -0,0 +1,282 @@
+use async_trait::async_trait;
+use dns_provider::{Action, Change, DnsProvider, Error, Plan, Record, RecordCreate, Zone};
+use crate::api::records::{self, RecordType, RecordUpdate};
+use crate::{HetznerClient, HetznerError};
+
+#[async_trait]
+impl DnsProvider for HetznerClient {
+    async fn get_zone_by_name(&self, name: &str) -> Result<Zone, Error> {
+        let zone = self.find_zone(name).await?;
+        Ok(Zone { id: zone.id, name: zone.name })
+    }
+
+    async fn get_records(&self, zone: &Zone) -> Result<Vec<Record>, Error> {
+        let records = self.get_all_records(zone.id.clone()).await?;
+        Ok(records.records.into_iter().map(|record| Record {
+            id: record.id,
+            zone_id: record.zone_id,
+            name: record.name,
+            record_type: String::from(record.record_type),
+            value: record.value,
+            ttl: record.ttl,
+            proxied: None,
+        }).collect())
+    }
+
+    async fn create_record(&self, zone: &Zone, record: &RecordCreate) -> Result<(), Error> {
+        self.create_record(
+            record.name.as_str(),
+            RecordType::from(record.record_type.as_str()),
+            record.value.as_str(),
+            record.ttl,
+            zone.id.clone(),
+        ).await?;
+        Ok(())
+    }
+
+    async fn update_record(&self, zone: &Zone, record_id: &str, record: &RecordCreate) -> Result<(), Error> {
+        self.update_record(
+            record_id,
+            record.name.as_str(),
+            RecordType::from(record.record_type.as_str()),
+            record.value.as_str(),
+            record.ttl,
+            zone.id.clone(),
+        ).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, _zone: &Zone, record_id: &str) -> Result<(), Error> {
+        self.delete_record(record_id).await?;
+        Ok(())
+    }
+
+    /// Writes the creates and updates of a zone with bulk requests if there
+    /// are several, see `apply_in_bulk`.
+    async fn apply_all(&self, plans: &[Plan]) -> Vec<Result<Action, Error>> {
+        let mut results: Vec<Option<Result<Action, Error>>> = plans.iter().map(|_| None).collect();
+        for (index, plan) in plans.iter().enumerate() {
+            if results[index].is_some() {
+                continue;
+            }
+            let zone: Vec<usize> = (index..plans.len()).filter(|other| plans[*other].zone.id == plan.zone.id).collect();
+            let zone_plans: Vec<&Plan> = zone.iter().map(|other| &plans[*other]).collect();
+            for (other, result) in zone.into_iter().zip(self.apply_in_bulk(&zone_plans).await) {
+                results[other] = Some(result);
+            }
+        }
+        results.into_iter().flatten().collect()
+    }
+}
+
+impl HetznerClient {
+    /// Applies plans of the same zone: all creates with one bulk request, all
+    /// updates with another, and the deletes of a plan once its writes
+    /// succeeded. A single write is sent on its own.
+    async fn apply_in_bulk(&self, plans: &[&Plan]) -> Vec<Result<Action, Error>> {
+        let mut creates = Vec::new();
+        let mut updates = Vec::new();
+        for (index, plan) in plans.iter().enumerate() {
+            for change in &plan.changes {
+                match change {
+                    Change::Create(new) => creates.push((index, record_create(&plan.zone, new))),
+                    Change::Update { old, new } => updates.push((index, RecordUpdate {
+                        id: old.id.clone(),
+                        record: record_create(&plan.zone, new),
+                    })),
+                    Change::Delete(_) | Change::Unchanged(_) => {}
+                }
+            }
+        }
+        if creates.len() + updates.len() < 2 {
+            let mut results = Vec::with_capacity(plans.len());
+            for plan in plans {
+                results.push(self.apply(plan).await);
+            }
+            return results;
+        }
+        let mut failures: Vec<Option<Error>> = plans.iter().map(|_| None).collect();
+        if !creates.is_empty() {
+            let records: Vec<records::RecordCreate> = creates.iter().map(|(_, record)| record.clone()).collect();
+            match self.create_records(&records).await {
+                Ok(created) => for invalid in &created.invalid_records {
+                    if let Some((index, _)) = creates.iter().find(|(_, record)| is_same(record, invalid)) {
+                        failures[*index] = Some(rejected(invalid, "create"));
+                    }
+                },
+                Err(e) => for (index, _) in &creates {
+                    failures[*index] = Some(bulk_error(&e));
+                },
+            }
+        }
+        if !updates.is_empty() {
+            let records: Vec<RecordUpdate> = updates.iter().map(|(_, update)| update.clone()).collect();
+            match self.update_records(&records).await {
+                Ok(updated) => for failed in &updated.failed_records {
+                    if let Some((index, _)) = updates.iter().find(|(_, update)| is_same(&update.record, failed)) {
+                        failures[*index] = Some(rejected(failed, "update"));
+                    }
+                },
+                Err(e) => for (index, _) in &updates {
+                    failures[*index] = Some(bulk_error(&e));
+                },
+            }
+        }
+        let mut results = Vec::with_capacity(plans.len());
+        for (plan, failure) in plans.iter().zip(failures) {
+            results.push(match failure {
+                Some(e) => Err(e),
+                None => self.delete_records(plan).await.map(|_| plan.action()),
+            });
+        }
+        results
+    }
+
+    async fn delete_records(&self, plan: &Plan) -> Result<(), Error> {
+        for change in &plan.changes {
+            if let Change::Delete(record) = change {
+                self.delete_record(record.id.as_str()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn record_create(zone: &Zone, record: &RecordCreate) -> records::RecordCreate {
+    records::RecordCreate {
+        record_type: RecordType::from(record.record_type.as_str()),
+        zone_id: zone.id.clone(),
+        name: record.name.clone(),
+        value: record.value.clone(),
+        ttl: record.ttl,
+    }
+}
+
+/// Hetzner echoes rejected records without id, so they are matched by their fields.
+fn is_same(record: &records::RecordCreate, rejected: &records::RecordCreate) -> bool {
+    record.name.eq_ignore_ascii_case(rejected.name.as_str())
+        && record.record_type == rejected.record_type
+        && record.value == rejected.value
+}
+
+fn rejected(record: &records::RecordCreate, operation: &str) -> Error {
+    Box::from(format!("Hetzner rejected the {} of {} {} with value {}", operation, record.record_type, record.name, record.value))
+}
+
+/// The error of a bulk request for each of its records, keeping the kind of
+/// error so e.g. rejected credentials are still recognized.
+fn bulk_error(error: &HetznerError) -> Error {
+    Box::new(match error {
+        HetznerError::Auth(message) => HetznerError::Auth(message.clone()),
+        HetznerError::NotFound(message) => HetznerError::NotFound(message.clone()),
+        HetznerError::RateLimited { retry_after } => HetznerError::RateLimited { retry_after: *retry_after },
+        HetznerError::Status { status, message } => HetznerError::Status { status: *status, message: message.clone() },
+        HetznerError::Decode(_) | HetznerError::Transport(_) => return Box::from(error.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_client;
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+
+    fn zone() -> Zone {
+        Zone { id: String::from("bulk-zone"), name: String::from("example.com") }
+    }
+
+    fn new_record(name: &str, value: &str) -> RecordCreate {
+        RecordCreate {
+            name: String::from(name),
+            record_type: String::from("A"),
+            value: String::from(value),
+            ttl: Some(60),
+            proxied: None,
+        }
+    }
+
+    fn old_record(id: &str, name: &str, value: &str) -> Record {
+        Record {
+            id: String::from(id),
+            zone_id: String::from("bulk-zone"),
+            name: String::from(name),
+            record_type: String::from("A"),
+            value: String::from(value),
+            ttl: Some(60),
+            proxied: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_all_in_bulk() {
+        let created = mock("POST", "/records/bulk")
+            .match_body(Matcher::Json(json!({"records": [
+                {"type": "A", "zone_id": "bulk-zone", "name": "vpn", "value": "203.0.113.7", "ttl": 60},
+                {"type": "A", "zone_id": "bulk-zone", "name": "bad", "value": "203.0.113.7", "ttl": 60}
+            ]})))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "records": [],
+                "valid_records": [{"type": "A", "zone_id": "bulk-zone", "name": "vpn", "value": "203.0.113.7", "ttl": 60}],
+                "invalid_records": [{"type": "A", "zone_id": "bulk-zone", "name": "bad", "value": "203.0.113.7", "ttl": 60}]
+            }"#)
+            .expect(1)
+            .create();
+        let updated = mock("PUT", "/records/bulk")
+            .match_body(Matcher::Json(json!({"records": [
+                {"id": "1", "type": "A", "zone_id": "bulk-zone", "name": "home", "value": "203.0.113.7", "ttl": 60}
+            ]})))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"records": [], "failed_records": []}"#)
+            .expect(1)
+            .create();
+        let deleted = mock("DELETE", "/records/2").expect(1).create();
+        let plans = vec![
+            Plan {
+                zone: zone(),
+                changes: vec![
+                    Change::Update { old: old_record("1", "home", "192.0.2.1"), new: new_record("home", "203.0.113.7") },
+                    Change::Delete(old_record("2", "home", "192.0.2.2")),
+                ],
+            },
+            Plan { zone: zone(), changes: vec![Change::Create(new_record("vpn", "203.0.113.7"))] },
+            Plan { zone: zone(), changes: vec![Change::Create(new_record("bad", "203.0.113.7"))] },
+        ];
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            let results = test_client().apply_all(&plans).await;
+            assert_eq!(Action::Updated, *results[0].as_ref().unwrap());
+            assert_eq!(Action::Created, *results[1].as_ref().unwrap());
+            assert_eq!(
+                "Hetzner rejected the create of A bad with value 203.0.113.7",
+                results[2].as_ref().err().unwrap().to_string()
+            );
+        });
+        created.assert();
+        updated.assert();
+        deleted.assert();
+    }
+
+    #[test]
+    fn test_apply_all_keeps_auth_errors() {
+        let _m = mock("POST", "/records/bulk")
+            .match_body(Matcher::PartialJsonString(String::from(r#"{"records": [{"zone_id": "auth-zone"}]}"#)))
+            .with_status(401)
+            .with_body(r#"{"message": "Invalid authentication credentials"}"#)
+            .create();
+        let zone = Zone { id: String::from("auth-zone"), name: String::from("example.org") };
+        let plans = vec![
+            Plan { zone: zone.clone(), changes: vec![Change::Create(new_record("a", "203.0.113.7"))] },
+            Plan { zone, changes: vec![Change::Create(new_record("b", "203.0.113.7"))] },
+        ];
+        let runtime = Runtime::new().expect("Init successful");
+        runtime.block_on(async move {
+            for result in test_client().apply_all(&plans).await {
+                let error = result.err().unwrap();
+                assert!(matches!(error.downcast_ref::<HetznerError>(), Some(HetznerError::Auth(_))));
+            }
+        });
+    }
+}